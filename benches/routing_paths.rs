@@ -0,0 +1,202 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+//! Benchmarks of the routing paths that every message, or every data update, goes through.
+//!
+//! The benchmarks need the unstable `test` crate. Run them with a nightly compiler using
+//! `cargo bench --features bench`, and compare against the previous release before publishing.
+//!
+//! A message is serialised once by its originator. Each relaying node checks the message it
+//! received the way the verifier's worker threads do, and passes its content on unchanged in a
+//! newly signed `HopMessage`.
+
+#![cfg(feature = "bench")]
+#![feature(test)]
+
+// For explanation of lint checks, run `rustc -W help` or see
+// https://github.com/maidsafe/QA/blob/master/Documentation/Rust%20Lint%20Checks.md
+#![forbid(bad_style, exceeding_bitshifts, mutable_transmutes, no_mangle_const_items,
+          unknown_crate_types, warnings)]
+#![deny(deprecated, drop_with_repr_extern, improper_ctypes, missing_docs,
+        non_shorthand_field_patterns, overflowing_literals, plugin_as_library,
+        private_no_mangle_fns, private_no_mangle_statics, stable_features, unconditional_recursion,
+        unknown_lints, unsafe_code, unused, unused_allocation, unused_attributes,
+        unused_comparisons, unused_features, unused_parens, while_true)]
+#![warn(trivial_casts, trivial_numeric_casts, unused_extern_crates, unused_import_braces,
+        unused_qualifications, unused_results)]
+#![allow(box_pointers, fat_ptr_transmutes, missing_copy_implementations,
+         missing_debug_implementations, variant_size_differences)]
+
+#[macro_use]
+extern crate maidsafe_utilities;
+extern crate rand;
+extern crate routing;
+extern crate sodiumoxide;
+extern crate test;
+extern crate xor_name;
+
+use maidsafe_utilities::serialisation::serialise;
+use routing::{Authority, Data, FullId, HopContent, HopMessage, Message, MessageId, PlainData,
+              RequestContent, RequestMessage, RoutingMessage, SignedMessage, StructuredData,
+              calculate_relocated_name};
+use routing::verifier::{self, CheckedContent};
+use sodiumoxide::crypto::sign;
+use test::Bencher;
+use xor_name::XorName;
+
+const PAYLOAD_SIZE: usize = 1024;
+const LARGE_PAYLOAD_SIZE: usize = 1024 * 1024;
+const OWNER_COUNT: usize = 100;
+const HOP_COUNT: usize = 10;
+
+fn put_request(payload_size: usize) -> RoutingMessage {
+    let data = Data::PlainData(PlainData::new(rand::random(), vec![7; payload_size]));
+    RoutingMessage::Request(RequestMessage {
+        src: Authority::ManagedNode(rand::random()),
+        dst: Authority::NaeManager(data.name()),
+        content: RequestContent::Put(data, MessageId::new()),
+    })
+}
+
+fn signed_put(payload_size: usize, full_id: &FullId) -> SignedMessage {
+    unwrap_result!(SignedMessage::new(put_request(payload_size), full_id))
+}
+
+// Wraps `content` in a `HopMessage` from `full_id` and serialises it, as `Core` does to relay it.
+fn relay(content: &HopContent, full_id: &FullId) -> Vec<u8> {
+    let hop_msg = unwrap_result!(HopMessage::new(content.clone(),
+                                                 *full_id.public_id().name(),
+                                                 false,
+                                                 10,
+                                                 None,
+                                                 full_id.signing_private_key()));
+    unwrap_result!(serialise(&Message::HopMessage(hop_msg)))
+}
+
+// Checks the received message as the verifier does, and returns the `HopMessage`.
+fn receive(bytes: &[u8], sender_key: &sign::PublicKey) -> HopMessage {
+    match unwrap_result!(verifier::check(bytes, Some(sender_key))) {
+        CheckedContent::Hop(hop_msg, _, _) => hop_msg,
+        _ => panic!("Not a hop message"),
+    }
+}
+
+#[bench]
+fn signed_message_new(b: &mut Bencher) {
+    let routing_msg = put_request(PAYLOAD_SIZE);
+    let full_id = FullId::new();
+    b.iter(|| unwrap_result!(SignedMessage::new(routing_msg.clone(), &full_id)));
+}
+
+#[bench]
+fn signed_message_check_integrity(b: &mut Bencher) {
+    let signed_msg = signed_put(PAYLOAD_SIZE, &FullId::new());
+    b.iter(|| unwrap_result!(signed_msg.check_integrity()));
+}
+
+#[bench]
+fn hop_message_relay(b: &mut Bencher) {
+    let content = unwrap_result!(HopContent::new(&signed_put(PAYLOAD_SIZE, &FullId::new())));
+    let full_id = FullId::new();
+    b.iter(|| relay(&content, &full_id));
+}
+
+#[bench]
+fn hop_message_check(b: &mut Bencher) {
+    let full_id = FullId::new();
+    let content = unwrap_result!(HopContent::new(&signed_put(PAYLOAD_SIZE, &FullId::new())));
+    let bytes = relay(&content, &full_id);
+    b.iter(|| receive(&bytes, full_id.public_id().signing_public_key()));
+}
+
+// Serialising a `Put` request with a 1 MB payload, done once by the originator.
+#[bench]
+fn large_message_serialise(b: &mut Bencher) {
+    let signed_msg = signed_put(LARGE_PAYLOAD_SIZE, &FullId::new());
+    b.bytes = LARGE_PAYLOAD_SIZE as u64;
+    b.iter(|| unwrap_result!(HopContent::new(&signed_msg)));
+}
+
+// Relaying a `Put` request with a 1 MB payload, done by each node that sends it to the next hop.
+#[bench]
+fn large_message_relay(b: &mut Bencher) {
+    let content = unwrap_result!(HopContent::new(&signed_put(LARGE_PAYLOAD_SIZE,
+                                                             &FullId::new())));
+    let full_id = FullId::new();
+    b.bytes = LARGE_PAYLOAD_SIZE as u64;
+    b.iter(|| relay(&content, &full_id));
+}
+
+// Checking a `Put` request with a 1 MB payload, done by each node that receives it.
+#[bench]
+fn large_message_check(b: &mut Bencher) {
+    let full_id = FullId::new();
+    let content = unwrap_result!(HopContent::new(&signed_put(LARGE_PAYLOAD_SIZE,
+                                                             &FullId::new())));
+    let bytes = relay(&content, &full_id);
+    b.bytes = LARGE_PAYLOAD_SIZE as u64;
+    b.iter(|| receive(&bytes, full_id.public_id().signing_public_key()));
+}
+
+// An update of a `StructuredData` owned by `OWNER_COUNT` keys, signed by all of them.
+#[bench]
+fn structured_data_validate_successor(b: &mut Bencher) {
+    let keys = (0..OWNER_COUNT).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+    let owners = keys.iter().map(|&(ref public_key, _)| *public_key).collect::<Vec<_>>();
+    let identifier: XorName = rand::random();
+    let current = unwrap_result!(StructuredData::new(0,
+                                                     identifier,
+                                                     0,
+                                                     vec![],
+                                                     owners.clone(),
+                                                     vec![],
+                                                     Some(&keys[0].1)));
+    let mut successor = unwrap_result!(StructuredData::new(0,
+                                                           identifier,
+                                                           1,
+                                                           vec![7; PAYLOAD_SIZE],
+                                                           owners,
+                                                           vec![],
+                                                           None));
+    for &(_, ref secret_key) in &keys {
+        let _ = unwrap_result!(successor.add_signature(secret_key));
+    }
+    b.iter(|| unwrap_result!(current.validate_self_against_successor(&successor)));
+}
+
+#[bench]
+fn relocated_name(b: &mut Bencher) {
+    let close_nodes = (0..8).map(|_| rand::random()).collect::<Vec<XorName>>();
+    let original_name = rand::random();
+    b.iter(|| unwrap_result!(calculate_relocated_name(close_nodes.clone(), &original_name)));
+}
+
+// Forwarding a request across `HOP_COUNT` nodes, each of which checks the message it received
+// against the previous node's key and relays its content unchanged under its own signature.
+#[bench]
+fn forward_across_hops(b: &mut Bencher) {
+    let full_ids = (0..HOP_COUNT + 1).map(|_| FullId::new()).collect::<Vec<_>>();
+    let content = unwrap_result!(HopContent::new(&signed_put(PAYLOAD_SIZE, &full_ids[0])));
+    let sent = relay(&content, &full_ids[0]);
+    b.iter(|| {
+        let mut bytes = sent.clone();
+        for hop in 1..full_ids.len() {
+            let hop_msg = receive(&bytes, full_ids[hop - 1].public_id().signing_public_key());
+            bytes = relay(hop_msg.content(), &full_ids[hop]);
+        }
+        bytes
+    });
+}
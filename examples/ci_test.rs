@@ -40,6 +40,7 @@ extern crate rand;
 extern crate rustc_serialize;
 extern crate docopt;
 extern crate sodiumoxide;
+extern crate kademlia_routing_table;
 extern crate routing;
 extern crate xor_name;
 extern crate lru_time_cache;
@@ -42,6 +42,7 @@ extern crate rustc_serialize;
 extern crate sodiumoxide;
 extern crate time;
 
+extern crate kademlia_routing_table;
 extern crate routing;
 extern crate xor_name;
 extern crate lru_time_cache;
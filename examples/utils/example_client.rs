@@ -81,9 +81,10 @@ impl ExampleClient {
         for it in self.receiver.iter() {
             match it {
                 Event::Response(ResponseMessage {
-                    content: ResponseContent::GetSuccess(data, _), .. }) => return Some(data),
+                    content: ResponseContent::GetSuccess(data, _), .. }, _) => return Some(data),
                 Event::Response(ResponseMessage {
-                    content: ResponseContent::GetFailure { external_error_indicator, .. }, .. }) => {
+                    content: ResponseContent::GetFailure { external_error_indicator, .. }, .. },
+                                _) => {
                     {
                         error!("Failed to Get {:?}: {:?}",
                                request.name(),
@@ -109,7 +110,7 @@ impl ExampleClient {
         // Wait for Put success event from Routing
         for it in self.receiver.iter() {
             if let Event::Response(ResponseMessage {
-                content: ResponseContent::PutSuccess(..), .. }) = it {
+                content: ResponseContent::PutSuccess(..), .. }, _) = it {
                 println!("Successfully stored {:?}", data_name);
                 break;
             }
@@ -15,8 +15,9 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use kademlia_routing_table;
 use lru_time_cache::LruCache;
-use xor_name::{XorName, closer_to_target};
+use xor_name::XorName;
 use routing::{RequestMessage, ResponseMessage, RequestContent, ResponseContent, MessageId,
               Authority, Node, Event, Data, DataRequest, InterfaceError};
 use maidsafe_utilities::serialisation::{serialise, deserialise};
@@ -78,7 +79,7 @@ impl ExampleNode {
 
             match event {
                 Event::Request(msg) => self.handle_request(msg),
-                Event::Response(msg) => self.handle_response(msg),
+                Event::Response(msg, _) => self.handle_response(msg),
                 Event::Churn { id, lost_close_node } => {
                     trace!("{:?} Received churn event {:?}", self, id);
                     self.handle_churn(id, lost_close_node)
@@ -88,6 +89,31 @@ impl ExampleNode {
                     trace!("{:?} Received connected event", self);
                     self.connected = true;
                 }
+                Event::JoinQueued { position, estimated_wait } => {
+                    trace!("{:?} Waiting to join: position {} in queue, estimated wait {:?}",
+                           self,
+                           position,
+                           estimated_wait);
+                }
+                Event::JoinFailed(stage) => {
+                    warn!("{:?} Joining the network failed at {:?}. Retrying.", self, stage);
+                }
+                Event::LookupComplete { id, target, unverified_nodes } => {
+                    trace!("{:?} Lookup {:?} for {:?} found {:?}",
+                           self,
+                           id,
+                           target,
+                           unverified_nodes);
+                }
+                Event::PeerBanned { public_key, endpoint } => {
+                    warn!("{:?} Banned peer {:?} at {:?}", self, public_key, endpoint);
+                }
+                Event::RateLimitExceeded { retry_after } => {
+                    warn!("{:?} Rate limit exceeded. Retry after {:?}.", self, retry_after);
+                }
+                Event::RouteTraced { id, dst, route } => {
+                    info!("{:?} Message {:?} to {:?} took route {:?}", self, id, dst, route);
+                }
             }
         }
     }
@@ -291,16 +317,7 @@ impl ExampleNode {
 
     /// Return the close group, including this node, sorted by closeness to the given name.
     fn group_by_closeness(&self, name: &XorName) -> Result<Vec<XorName>, InterfaceError> {
-        let mut close_grp = try!(self.node.close_group());
-        close_grp.push(try!(self.node.name()));
-        close_grp.sort_by(|lhs, rhs| {
-            if closer_to_target(lhs, rhs, name) {
-                ::std::cmp::Ordering::Less
-            } else {
-                ::std::cmp::Ordering::Greater
-            }
-        });
-        Ok(close_grp)
+        self.node.closest_known_nodes(name, kademlia_routing_table::group_size() + 1)
     }
 
     // While handling churn messages, we first "action" it ourselves and then
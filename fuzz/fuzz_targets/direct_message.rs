@@ -0,0 +1,44 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Decodes and checks a `Message` carrying a `DirectMessage`, including the signed `PublicId` in
+//! `ClientIdentify` and `NodeIdentify`, as the verifier threads do.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate maidsafe_utilities;
+extern crate routing;
+
+use maidsafe_utilities::serialisation::serialise;
+use routing::verifier::{self, CheckedContent};
+
+fuzz_target!(|data: &[u8]| {
+    match verifier::check(data, None) {
+        Ok(CheckedContent::Direct(direct_msg)) => {
+            let _ = serialise(&direct_msg);
+        }
+        Ok(CheckedContent::Identify(direct_msg, public_id)) => {
+            if let Ok(public_id) = public_id {
+                let _ = serialise(&public_id);
+            }
+            let _ = serialise(&direct_msg);
+        }
+        _ => (),
+    }
+});
@@ -0,0 +1,54 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Decrypts and decodes the endpoints sent in `Endpoints` and `GetPublicIdWithEndpoints`.
+//!
+//! The input is the serialised endpoints, as encrypted by a peer we have the keys for. It is also
+//! decrypted as is, with its first bytes as the nonce, as received from a peer using other keys.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate routing;
+extern crate sodiumoxide;
+
+use routing::decrypt_endpoints;
+use sodiumoxide::crypto::box_;
+
+fuzz_target!(|data: &[u8]| {
+    let (our_public_key, our_secret_key) = box_::gen_keypair();
+    let (their_public_key, their_secret_key) = box_::gen_keypair();
+
+    let nonce = box_::gen_nonce();
+    let encrypted_endpoints = box_::seal(data, &nonce, &our_public_key, &their_secret_key);
+    let _ = decrypt_endpoints(&encrypted_endpoints,
+                              nonce.0,
+                              &their_public_key,
+                              &our_secret_key);
+
+    if data.len() >= box_::NONCEBYTES {
+        let mut nonce_bytes = [0; box_::NONCEBYTES];
+        for (nonce_byte, byte) in nonce_bytes.iter_mut().zip(data) {
+            *nonce_byte = *byte;
+        }
+        let _ = decrypt_endpoints(&data[box_::NONCEBYTES..],
+                                  nonce_bytes,
+                                  &their_public_key,
+                                  &our_secret_key);
+    }
+});
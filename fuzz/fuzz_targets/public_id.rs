@@ -0,0 +1,41 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Decodes and checks a `NodeIdentify` carrying the input as its serialised `PublicId`, as the
+//! verifier threads do with the identity sent in `ClientIdentify` and `NodeIdentify`.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate maidsafe_utilities;
+extern crate routing;
+extern crate sodiumoxide;
+
+use maidsafe_utilities::serialisation::serialise;
+use routing::{DirectMessage, Message, verifier};
+use sodiumoxide::crypto::sign;
+
+fuzz_target!(|data: &[u8]| {
+    let node_identify = DirectMessage::NodeIdentify {
+        serialised_public_id: data.to_vec(),
+        signature: sign::Signature([0; sign::SIGNATUREBYTES]),
+    };
+    if let Ok(bytes) = serialise(&Message::DirectMessage(node_identify)) {
+        let _ = verifier::check(&bytes, None);
+    }
+});
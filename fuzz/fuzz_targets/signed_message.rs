@@ -0,0 +1,40 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Decodes and checks a `Message` carrying a `HopMessage`, as the verifier threads do, and reads
+//! the `SignedMessage` it contains.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate maidsafe_utilities;
+extern crate routing;
+
+use maidsafe_utilities::serialisation::serialise;
+use routing::verifier::{self, CheckedContent};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(CheckedContent::Hop(_, signed_msg, _)) = verifier::check(data, None) {
+        let _ = signed_msg.check_integrity();
+        let routing_msg = signed_msg.content();
+        let _ = (routing_msg.src(), routing_msg.dst(), routing_msg.data());
+        let _ = routing_msg.message_id();
+        let _ = signed_msg.content_digest();
+        let _ = serialise(&signed_msg);
+    }
+});
@@ -0,0 +1,190 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Writes valid messages of each fuzzed type to `corpus/<target>`, as starting points for the
+//! fuzzer.
+//!
+//! Run with `cargo run --bin generate_corpus` before fuzzing a target for the first time.
+
+#[macro_use]
+extern crate maidsafe_utilities;
+extern crate crust;
+extern crate rand;
+extern crate routing;
+extern crate rustc_serialize;
+extern crate sodiumoxide;
+
+mod peer_key;
+
+use crust::Endpoint;
+use maidsafe_utilities::serialisation::serialise;
+use routing::{Authority, BootstrapDenyReason, Data, DirectMessage, FullId, HopContent, HopMessage,
+              Message, MessageId, PlainData, RequestContent, RequestMessage, ResponseContent,
+              ResponseMessage, RoutingMessage, SignedMessage, StructuredData, TracedHop};
+use rustc_serialize::Encodable;
+use sodiumoxide::crypto::{box_, sign};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+fn write_corpus<T: Encodable>(target: &str, values: &[T]) {
+    let mut dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    dir.push("corpus");
+    dir.push(target);
+    unwrap_result!(fs::create_dir_all(&dir));
+    for (i, value) in values.iter().enumerate() {
+        let mut file = unwrap_result!(File::create(dir.join(format!("valid-{}", i))));
+        unwrap_result!(file.write_all(&unwrap_result!(serialise(value))));
+    }
+}
+
+fn signed_messages(full_id: &FullId) -> Vec<SignedMessage> {
+    let data = Data::PlainData(PlainData::new(rand::random(), vec![7; 1024]));
+    let put = RequestMessage {
+        src: Authority::Client {
+            client_key: *full_id.public_id().signing_public_key(),
+            proxy_node_name: rand::random(),
+        },
+        dst: Authority::NaeManager(data.name()),
+        content: RequestContent::Put(data.clone(), MessageId::new()),
+    };
+    let get_close_group = RequestMessage {
+        src: Authority::ManagedNode(*full_id.public_id().name()),
+        dst: Authority::NodeManager(rand::random()),
+        content: RequestContent::GetCloseGroup,
+    };
+    let get_success = ResponseMessage {
+        src: Authority::NaeManager(data.name()),
+        dst: Authority::ManagedNode(rand::random()),
+        content: ResponseContent::GetSuccess(data, MessageId::new()),
+    };
+    let find_node = ResponseMessage {
+        src: Authority::ManagedNode(*full_id.public_id().name()),
+        dst: Authority::ManagedNode(rand::random()),
+        content: ResponseContent::FindNode {
+            target: rand::random(),
+            id: MessageId::new(),
+            close_nodes: vec![FullId::new().public_id().clone(), FullId::new().public_id().clone()],
+        },
+    };
+    vec![RoutingMessage::Request(put),
+         RoutingMessage::Request(get_close_group),
+         RoutingMessage::Response(get_success),
+         RoutingMessage::Response(find_node)]
+        .into_iter()
+        .map(|routing_msg| unwrap_result!(SignedMessage::new(routing_msg, full_id)))
+        .collect()
+}
+
+fn hop_messages(full_id: &FullId) -> Vec<HopMessage> {
+    signed_messages(full_id)
+        .iter()
+        .flat_map(|signed_msg| {
+            let traced_hop = unwrap_result!(TracedHop::new(signed_msg, None, full_id));
+            vec![None, Some(vec![traced_hop])].into_iter().map(move |trace| {
+                unwrap_result!(HopMessage::new(unwrap_result!(HopContent::new(signed_msg)),
+                                               *full_id.public_id().name(),
+                                               true,
+                                               10,
+                                               trace,
+                                               full_id.signing_private_key()))
+            })
+        })
+        .collect()
+}
+
+fn direct_messages(full_id: &FullId) -> Vec<DirectMessage> {
+    let serialised_public_id = unwrap_result!(serialise(full_id.public_id()));
+    let signature = sign::sign_detached(&serialised_public_id, full_id.signing_private_key());
+    let connect = RequestMessage {
+        src: Authority::ManagedNode(rand::random()),
+        dst: Authority::NaeManager(rand::random()),
+        content: RequestContent::Connect,
+    };
+    let signed_msg = unwrap_result!(SignedMessage::new(RoutingMessage::Request(connect), full_id));
+    vec![DirectMessage::BootstrapIdentify {
+             public_id: full_id.public_id().clone(),
+             current_quorum_size: 5,
+         },
+         DirectMessage::BootstrapDeny { reason: BootstrapDenyReason::JoinQueueFull },
+         DirectMessage::BootstrapQueued {
+             position: 3,
+             estimated_wait_secs: 60,
+         },
+         DirectMessage::ClientIdentify {
+             serialised_public_id: serialised_public_id.clone(),
+             signature: signature,
+             client_restriction: true,
+         },
+         DirectMessage::Heartbeat,
+         DirectMessage::HeartbeatResponse,
+         DirectMessage::HopAck { digest: *unwrap_result!(HopContent::new(&signed_msg)).digest() },
+         DirectMessage::RateLimitExceeded { retry_after_ms: Some(1000) },
+         DirectMessage::NodeIdentify {
+             serialised_public_id: serialised_public_id,
+             signature: signature,
+         }]
+}
+
+fn structured_data() -> Vec<StructuredData> {
+    let keys = (0..3).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+    let owners = keys.iter().map(|&(ref public_key, _)| *public_key).collect::<Vec<_>>();
+    let mut update = unwrap_result!(StructuredData::new(1,
+                                                        rand::random(),
+                                                        1,
+                                                        vec![7; 100],
+                                                        owners.clone(),
+                                                        vec![],
+                                                        Some(&keys[0].1)));
+    let _ = unwrap_result!(update.add_signature(&keys[1].1));
+    let transfer = unwrap_result!(StructuredData::new(1,
+                                                      rand::random(),
+                                                      2,
+                                                      vec![],
+                                                      vec![sign::gen_keypair().0],
+                                                      owners,
+                                                      Some(&keys[2].1)));
+    vec![update, transfer]
+}
+
+fn main() {
+    // The messages are sent by the peer whose key the targets check hop signatures against.
+    let full_id = FullId::with_keys(box_::gen_keypair(), peer_key::keypair());
+
+    // The message targets all decode a `Message`, as received from a connection.
+    let direct_msgs = direct_messages(&full_id)
+                          .into_iter()
+                          .map(Message::DirectMessage)
+                          .collect::<Vec<_>>();
+    let hop_msgs = hop_messages(&full_id)
+                       .into_iter()
+                       .map(Message::HopMessage)
+                       .collect::<Vec<_>>();
+    write_corpus("direct_message", &direct_msgs);
+    write_corpus("hop_message", &hop_msgs);
+    write_corpus("signed_message", &hop_msgs);
+    let mut messages = direct_msgs;
+    messages.extend(hop_msgs);
+    write_corpus("message", &messages);
+    write_corpus("structured_data", &structured_data());
+    write_corpus("public_id", &[full_id.public_id().clone(), FullId::new().public_id().clone()]);
+
+    // The endpoints target encrypts its input itself.
+    let endpoints = vec![Endpoint::Tcp(unwrap_result!("127.0.0.1:5483".parse())),
+                         Endpoint::Utp(unwrap_result!("[::1]:5484".parse()))];
+    write_corpus("endpoints", &[endpoints]);
+}
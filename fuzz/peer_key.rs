@@ -0,0 +1,29 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! The key of the previous hop the fuzz targets check hop messages against. The corpus generator
+//! signs its hop messages with the matching secret key, so that inputs mutated from them can get
+//! past the hop signature check.
+
+use sodiumoxide::crypto::sign;
+
+const PEER_KEY_SEED: [u8; sign::SEEDBYTES] = [7; sign::SEEDBYTES];
+
+/// The key pair of the previous hop.
+pub fn keypair() -> (sign::PublicKey, sign::SecretKey) {
+    sign::keypair_from_seed(&sign::Seed(PEER_KEY_SEED))
+}
@@ -17,8 +17,8 @@
 
 use crust::{Endpoint, Port};
 use std::collections::HashSet;
-use std::net::SocketAddr;
 use ip::IpAddr;
+use utils;
 
 /// Holds the endpoints crust informs us we're accepting on.
 pub struct Acceptors {
@@ -40,7 +40,7 @@ impl Acceptors {
 
     /// If disconnected on our first call to connect, set our bootstrap nodes' ip from the returned endpoint.
     pub fn set_bootstrap_ip(&mut self, bootstrap_endpoint: Endpoint) {
-        self.bootstrap_ip = Some(Self::ip_from_socketaddr(bootstrap_endpoint.get_address()));
+        self.bootstrap_ip = Some(utils::ip_from_socketaddr(bootstrap_endpoint.get_address()));
     }
 
     /// The tcp port from the endpoint returned by crust on a call to start_accepting with our default tcp port.
@@ -72,14 +72,14 @@ impl Acceptors {
             Endpoint::Tcp(socket_addr) => {
                 if let Some(ref port) = self.tcp_accepting_port {
                     let _ = self.endpoints
-                                .insert(Endpoint::new(Self::ip_from_socketaddr(socket_addr),
+                                .insert(Endpoint::new(utils::ip_from_socketaddr(socket_addr),
                                                       port.clone()));
                 }
             }
             Endpoint::Utp(socket_addr) => {
                 if let Some(ref port) = self.utp_accepting_port {
                     let _ = self.endpoints
-                                .insert(Endpoint::new(Self::ip_from_socketaddr(socket_addr),
+                                .insert(Endpoint::new(utils::ip_from_socketaddr(socket_addr),
                                                       port.clone()));
                 }
             }
@@ -90,11 +90,4 @@ impl Acceptors {
     pub fn endpoints(&self) -> Vec<Endpoint> {
         self.endpoints.iter().cloned().collect()
     }
-
-    fn ip_from_socketaddr(addr: SocketAddr) -> IpAddr {
-        match addr {
-            SocketAddr::V4(address) => IpAddr::V4(*address.ip()),
-            SocketAddr::V6(address) => IpAddr::V6(*address.ip()),
-        }
-    }
 }
@@ -16,9 +16,12 @@
 // relating to use of the SAFE Network Software.
 
 use authority::Authority;
+use diagnostics::RoutingTableSnapshot;
 use error::InterfaceError;
 use std::sync::mpsc::Sender;
 use messages::{RequestContent, RoutingMessage};
+use sodiumoxide::crypto::sign;
+use types::MessageId;
 use xor_name::XorName;
 
 /// An Action initiates a message flow < A | B > where we are (a part of) A.
@@ -31,11 +34,13 @@ use xor_name::XorName;
 pub enum Action {
     NodeSendMessage {
         content: RoutingMessage,
+        trace_route: bool,
         result_tx: Sender<Result<(), InterfaceError>>,
     },
     ClientSendRequest {
         content: RequestContent,
         dst: Authority,
+        trace_route: bool,
         result_tx: Sender<Result<(), InterfaceError>>,
     },
     CloseGroup {
@@ -44,23 +49,77 @@ pub enum Action {
     Name {
         result_tx: Sender<XorName>,
     },
+    ClosestNodes {
+        name: XorName,
+        count: usize,
+        result_tx: Sender<Vec<XorName>>,
+    },
+    IsResponsibleFor {
+        name: XorName,
+        result_tx: Sender<bool>,
+    },
+    FindNodes {
+        target: XorName,
+        id: MessageId,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    NetworkSize {
+        result_tx: Sender<u64>,
+    },
+    RoutingTable {
+        result_tx: Sender<RoutingTableSnapshot>,
+    },
+    Tick,
+    MessageChecked,
+    ResourceProofSolved {
+        seed: Vec<u8>,
+        data: Vec<u8>,
+        nonce: u64,
+        member_name: XorName,
+        client_key: sign::PublicKey,
+        proxy_name: XorName,
+    },
     Terminate,
 }
 
 impl ::std::fmt::Debug for Action {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         match *self {
-            Action::NodeSendMessage { ref content, .. } => {
-                write!(f, "Action::NodeSendMessage {{ {:?}, result_tx }}", content)
+            Action::NodeSendMessage { ref content, trace_route, .. } => {
+                write!(f,
+                       "Action::NodeSendMessage {{ {:?}, trace_route: {}, result_tx }}",
+                       content,
+                       trace_route)
             }
-            Action::ClientSendRequest { ref content, ref dst, .. } => {
+            Action::ClientSendRequest { ref content, ref dst, trace_route, .. } => {
                 write!(f,
-                       "Action::ClientSendRequest {{ {:?}, dst: {:?}, result_tx }}",
+                       "Action::ClientSendRequest {{ {:?}, dst: {:?}, trace_route: {}, \
+                        result_tx }}",
                        content,
-                       dst)
+                       dst,
+                       trace_route)
             }
             Action::CloseGroup { .. } => write!(f, "Action::CloseGroup"),
             Action::Name{ .. } => write!(f, "Action::Name"),
+            Action::ClosestNodes { ref name, count, .. } => {
+                write!(f, "Action::ClosestNodes {{ {:?}, count: {} }}", name, count)
+            }
+            Action::IsResponsibleFor { ref name, .. } => {
+                write!(f, "Action::IsResponsibleFor {{ {:?} }}", name)
+            }
+            Action::FindNodes { ref target, ref id, .. } => {
+                write!(f, "Action::FindNodes {{ {:?}, id: {:?}, result_tx }}", target, id)
+            }
+            Action::NetworkSize { .. } => write!(f, "Action::NetworkSize"),
+            Action::RoutingTable { .. } => write!(f, "Action::RoutingTable"),
+            Action::Tick => write!(f, "Action::Tick"),
+            Action::MessageChecked => write!(f, "Action::MessageChecked"),
+            Action::ResourceProofSolved { ref member_name, nonce, .. } => {
+                write!(f,
+                       "Action::ResourceProofSolved {{ {:?}, nonce: {} }}",
+                       member_name,
+                       nonce)
+            }
             Action::Terminate => write!(f, "Action::Terminate"),
         }
     }
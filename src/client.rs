@@ -20,6 +20,7 @@ use std::sync::mpsc::{Receiver, Sender, channel};
 
 use id::FullId;
 use action::Action;
+use config::Config;
 use event::Event;
 use core::Core;
 use data::{Data, DataRequest};
@@ -57,7 +58,10 @@ impl Client {
         sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
 
         // start the handler for routing with a restriction to become a full node
-        let (action_sender, raii_joiner) = try!(Core::new(event_sender, true, keys));
+        let (action_sender, raii_joiner) = try!(Core::new(event_sender,
+                                                          true,
+                                                          keys,
+                                                          Config::default()));
 
         let (tx, rx) = channel();
 
@@ -92,10 +96,44 @@ impl Client {
         self.send_action(RequestContent::Delete(data, MessageId::new()), dst)
     }
 
+    /// Ask the members of the group authority `dst` for their `PublicId`s.
+    ///
+    /// The response is raised as an `Event::Response` with `ResponseContent::GetGroupIds` and the
+    /// given `id`, and can be used to check the signers of the group's other responses with
+    /// `ResponseMessage::verify_group_members`. Its own proof can only be checked against the IDs
+    /// it contains, so a proxy node could fabricate a consistent response; comparing the IDs
+    /// received through different proxy nodes detects that.
+    pub fn send_get_group_ids_request(&self,
+                                      dst: Authority,
+                                      id: MessageId)
+                                      -> Result<(), InterfaceError> {
+        self.send_action(RequestContent::GetGroupIds { id: id }, dst)
+    }
+
+    /// Send a request and have the nodes relaying it record its route, for debugging.
+    ///
+    /// The member of the destination authority closest to its name returns the route, which is
+    /// raised as an `Event::RouteTraced`.
+    pub fn send_traced_request(&self,
+                               dst: Authority,
+                               content: RequestContent)
+                               -> Result<(), InterfaceError> {
+        self.send_request(content, dst, true)
+    }
+
     fn send_action(&self, content: RequestContent, dst: Authority) -> Result<(), InterfaceError> {
+        self.send_request(content, dst, false)
+    }
+
+    fn send_request(&self,
+                    content: RequestContent,
+                    dst: Authority,
+                    trace_route: bool)
+                    -> Result<(), InterfaceError> {
         let action = Action::ClientSendRequest {
             content: content,
             dst: dst,
+            trace_route: trace_route,
             result_tx: self.interface_result_tx.clone(),
         };
 
@@ -0,0 +1,285 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use data::Data;
+use error::RoutingError;
+use std::time::Duration;
+use structured_data::MAX_STRUCTURED_DATA_SIZE_IN_BYTES;
+
+/// Parameters of a routing node's behaviour.
+///
+/// `Config::default()` provides the values used by `Node::new`.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The number of leading zero bits a joining node's resource proof hash needs to have.
+    ///
+    /// Each additional bit doubles the expected amount of work. If this and
+    /// `resource_proof_size` are both zero, joining nodes are not challenged.
+    pub resource_proof_difficulty: u8,
+    /// The number of bytes a joining node needs to send to each member of its new close group as
+    /// part of its resource proof.
+    pub resource_proof_size: usize,
+    /// The highest difficulty of a resource proof challenge we accept from our new close group.
+    ///
+    /// Challenges exceeding this or `max_resource_proof_size` are ignored.
+    pub max_resource_proof_difficulty: u8,
+    /// The largest resource proof data size we accept in a challenge from our new close group.
+    pub max_resource_proof_size: usize,
+    /// The maximum number of joining nodes that can wait for us to become their proxy.
+    ///
+    /// Further nodes are denied until a place in the queue becomes available.
+    pub max_join_queue_len: usize,
+    /// The time a joining node waits for each stage of its relocation to complete, before it gives
+    /// up and restarts from bootstrapping.
+    pub join_timeout: Duration,
+    /// The time after which a routing table bucket that hasn't gained any nodes is refreshed by
+    /// looking up a random name in its range.
+    pub bucket_refresh_interval: Duration,
+    /// The time after which a routing table connection we haven't received anything on is probed
+    /// with a heartbeat.
+    pub heartbeat_interval: Duration,
+    /// The number of consecutive unanswered heartbeats after which a connection is dropped.
+    pub heartbeat_miss_threshold: usize,
+    /// Whether to prefer lower-latency nodes among equally close ones when forwarding messages
+    /// that are not addressed to a group.
+    ///
+    /// If enabled, all routing table connections are probed with heartbeats to measure their
+    /// round trip times, not only the quiet ones.
+    pub latency_aware_forwarding: bool,
+    /// The score at which a peer's connection is dropped.
+    ///
+    /// Each peer starts with a score of zero, which is decreased whenever it violates the
+    /// protocol, e. g. by sending undecodable messages or invalid signatures.
+    pub peer_drop_score: i32,
+    /// The score at which a peer is disconnected and banned. This must not be greater than
+    /// `peer_drop_score`.
+    pub peer_ban_score: i32,
+    /// The time for which a banned peer's key and IP address are refused. This is also the time
+    /// after which a peer's score is reset if it hasn't violated the protocol again.
+    pub peer_ban_duration: Duration,
+    /// The number of messages per second a client can send through us as its proxy node. This
+    /// must be greater than zero.
+    pub client_message_rate: u64,
+    /// The number of bytes per second a client can send through us as its proxy node. This must
+    /// be greater than zero.
+    pub client_byte_rate: u64,
+    /// The time for which a client can save up unused message and byte allowances, i. e. the
+    /// size of the bursts it can send is the rates multiplied by this. This must be at least one
+    /// second.
+    pub client_rate_burst: Duration,
+    /// The number of consecutive messages exceeding its rate limit after which a client is
+    /// disconnected. The rejected messages before that are answered with an error.
+    pub client_rate_limit_violations: usize,
+    /// The maximum number of clients we act as the proxy node for. Joining nodes are not counted.
+    pub max_clients: usize,
+    /// The time a client needs to have been idle for before it can be disconnected to make room
+    /// for a new one, once `max_clients` is reached. The longest idle client is evicted first.
+    pub client_eviction_idle_time: Duration,
+    /// The maximum size in bytes of a serialised message relayed through the network.
+    pub max_hop_message_size: usize,
+    /// The maximum size in bytes of a serialised message exchanged directly between peers, e. g.
+    /// to identify themselves.
+    pub max_direct_message_size: usize,
+    /// The maximum payload size in bytes of `StructuredData`.
+    pub max_structured_data_size: usize,
+    /// The maximum payload size in bytes of `ImmutableData`.
+    pub max_immutable_data_size: usize,
+    /// The maximum payload size in bytes of `PlainData`.
+    pub max_plain_data_size: usize,
+    /// Whether to request acknowledgements from the next hops of the messages we relay, and resend
+    /// messages that are not acknowledged to alternative next hops.
+    pub hop_acks: bool,
+    /// The time after which a message that hasn't been acknowledged by the next hop is resent.
+    pub hop_ack_timeout: Duration,
+    /// The maximum number of times a message is resent to alternative next hops.
+    pub hop_ack_max_resends: usize,
+    /// The maximum number of relayed messages awaiting acknowledgements at the same time. Further
+    /// messages are relayed without being resent if they are not acknowledged.
+    pub max_pending_acks: usize,
+    /// The maximum number of hops the messages we send can be relayed over. Messages that reach
+    /// the limit are dropped, unless they have arrived at their destination.
+    pub hop_limit: u8,
+    /// The maximum total size in bytes of the messages queued for sending to a single peer.
+    /// Messages that don't fit after dropping queued messages of lower priority are refused with
+    /// `InterfaceError::SendQueueFull`.
+    pub max_send_queue_size: usize,
+    /// The number of bytes per second sent to a single peer. This must be greater than zero.
+    pub send_byte_rate: u64,
+    /// The time span whose allowance of bytes can be sent to a peer at once. Messages held back by
+    /// the byte rate are sent on the next one second tick, so this must be at least one second.
+    pub send_rate_burst: Duration,
+    /// The maximum number of message digests each message filter remembers.
+    pub message_filter_capacity: usize,
+    /// The maximum number of digests each message filter remembers of the messages received from
+    /// a single peer connection. Beyond that, the peer's oldest digests are forgotten before they
+    /// expire.
+    pub message_filter_peer_share: usize,
+    /// The maximum number of group messages whose signatures are accumulated at the same time.
+    pub accumulator_capacity: usize,
+    /// The maximum number of group messages whose signatures received from a single peer
+    /// connection are accumulated at the same time. Beyond that, the signatures the peer
+    /// delivered for its oldest messages are forgotten before they expire.
+    pub accumulator_peer_share: usize,
+    /// The number of threads that decode incoming messages, verify their signatures and decrypt
+    /// the endpoints of the nodes we connect to.
+    pub verifier_threads: usize,
+    /// The maximum total size in bytes of the messages received on a single connection that are
+    /// waiting to be checked by the verifier threads. Further messages from that peer are dropped
+    /// and it is penalised until the queued ones have been checked. This must be at least
+    /// `max_hop_message_size` and `max_direct_message_size`.
+    pub max_verifier_queue_size: usize,
+}
+
+impl Config {
+    /// Return an error if the parameters are inconsistent.
+    pub fn check(&self) -> Result<(), RoutingError> {
+        if self.peer_ban_score > self.peer_drop_score {
+            error!("Invalid config: peer_ban_score {} is greater than peer_drop_score {}.",
+                   self.peer_ban_score,
+                   self.peer_drop_score);
+            return Err(RoutingError::InvalidConfig);
+        }
+        if self.client_message_rate == 0 || self.client_byte_rate == 0 ||
+           self.send_byte_rate == 0 {
+            error!("Invalid config: client_message_rate {}, client_byte_rate {} and \
+                    send_byte_rate {} must be greater than zero.",
+                   self.client_message_rate,
+                   self.client_byte_rate,
+                   self.send_byte_rate);
+            return Err(RoutingError::InvalidConfig);
+        }
+        if self.client_rate_burst < Duration::from_secs(1) ||
+           self.send_rate_burst < Duration::from_secs(1) {
+            error!("Invalid config: client_rate_burst {:?} and send_rate_burst {:?} must be at \
+                    least one second.",
+                   self.client_rate_burst,
+                   self.send_rate_burst);
+            return Err(RoutingError::InvalidConfig);
+        }
+        if self.max_verifier_queue_size < self.max_hop_message_size ||
+           self.max_verifier_queue_size < self.max_direct_message_size {
+            error!("Invalid config: max_verifier_queue_size {} is less than max_hop_message_size \
+                    {} or max_direct_message_size {}.",
+                   self.max_verifier_queue_size,
+                   self.max_hop_message_size,
+                   self.max_direct_message_size);
+            return Err(RoutingError::InvalidConfig);
+        }
+        Ok(())
+    }
+
+    /// The maximum payload size in bytes of the given data's type.
+    pub fn max_data_size(&self, data: &Data) -> usize {
+        match *data {
+            Data::StructuredData(_) => self.max_structured_data_size,
+            Data::ImmutableData(_) => self.max_immutable_data_size,
+            Data::PlainData(_) => self.max_plain_data_size,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            resource_proof_difficulty: 16,
+            resource_proof_size: 100 * 1024,
+            max_resource_proof_difficulty: 20,
+            max_resource_proof_size: 1024 * 1024,
+            max_join_queue_len: 16,
+            join_timeout: Duration::from_secs(120),
+            bucket_refresh_interval: Duration::from_secs(600),
+            heartbeat_interval: Duration::from_secs(30),
+            heartbeat_miss_threshold: 3,
+            latency_aware_forwarding: false,
+            peer_drop_score: -100,
+            peer_ban_score: -200,
+            peer_ban_duration: Duration::from_secs(3600),
+            client_message_rate: 50,
+            client_byte_rate: 1024 * 1024,
+            client_rate_burst: Duration::from_secs(10),
+            client_rate_limit_violations: 20,
+            max_clients: 200,
+            client_eviction_idle_time: Duration::from_secs(300),
+            max_hop_message_size: 2 * 1024 * 1024,
+            max_direct_message_size: 64 * 1024,
+            max_structured_data_size: MAX_STRUCTURED_DATA_SIZE_IN_BYTES,
+            max_immutable_data_size: 1024 * 1024,
+            max_plain_data_size: 1024 * 1024,
+            hop_acks: false,
+            hop_ack_timeout: Duration::from_secs(5),
+            hop_ack_max_resends: 3,
+            max_pending_acks: 1000,
+            hop_limit: 32,
+            max_send_queue_size: 8 * 1024 * 1024,
+            send_byte_rate: 10 * 1024 * 1024,
+            send_rate_burst: Duration::from_secs(1),
+            message_filter_capacity: 100_000,
+            message_filter_peer_share: 10_000,
+            accumulator_capacity: 10_000,
+            accumulator_peer_share: 1000,
+            verifier_threads: 4,
+            max_verifier_queue_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Config;
+    use std::time::Duration;
+
+    #[test]
+    fn check_scores() {
+        let mut config = Config::default();
+        assert!(config.check().is_ok());
+        config.peer_ban_score = config.peer_drop_score;
+        assert!(config.check().is_ok());
+        config.peer_ban_score = config.peer_drop_score + 1;
+        assert!(config.check().is_err());
+    }
+
+    #[test]
+    fn check_rates() {
+        let mut config = Config::default();
+        config.send_byte_rate = 0;
+        assert!(config.check().is_err());
+
+        let mut config = Config::default();
+        config.client_message_rate = 0;
+        assert!(config.check().is_err());
+
+        let mut config = Config::default();
+        config.client_byte_rate = 0;
+        assert!(config.check().is_err());
+
+        let mut config = Config::default();
+        config.send_rate_burst = Duration::from_millis(999);
+        assert!(config.check().is_err());
+        config.send_rate_burst = Duration::from_secs(1);
+        assert!(config.check().is_ok());
+        config.client_rate_burst = Duration::from_secs(0);
+        assert!(config.check().is_err());
+    }
+
+    #[test]
+    fn check_verifier_queue_size() {
+        let mut config = Config::default();
+        config.max_verifier_queue_size = config.max_hop_message_size;
+        assert!(config.check().is_ok());
+        config.max_verifier_queue_size = config.max_hop_message_size - 1;
+        assert!(config.check().is_err());
+    }
+}
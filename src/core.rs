@@ -15,8 +15,8 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use accumulator::Accumulator;
 use crust;
+use ip::IpAddr;
 use itertools::Itertools;
 use kademlia_routing_table;
 use kademlia_routing_table::{NodeInfo, RoutingTable};
@@ -26,26 +26,41 @@ use maidsafe_utilities::serialisation;
 use maidsafe_utilities::thread::RaiiThreadJoiner;
 use message_filter::MessageFilter;
 use sodiumoxide::crypto::{box_, hash, sign};
+use std::cmp;
 use std::io;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::{Debug, Formatter};
 use std::sync::mpsc;
 use std::thread;
-use time::Duration;
+use time::{Duration, SteadyTime};
 use xor_name::XorName;
 
 use acceptors::Acceptors;
 use action::Action;
 use authority::Authority;
+use config::Config;
+use hop_acks::{PendingAcks, Timeout};
 use data::{Data, DataRequest};
+use diagnostics::{ConnectionState, RoutingTableEntry, RoutingTableSnapshot};
+use digest_filter::DigestFilter;
 use error::{RoutingError, InterfaceError};
-use event::Event;
+use event::{Event, JoinStage};
 use id::{FullId, PublicId};
+use join_queue::JoinQueue;
+use liveness::Liveness;
+use lookup::Lookup;
+use rate_limit::RateLimiter;
+use reputation::{BanList, PeerId, Reputation, Verdict, Violation};
+use send_queue::{Priority, SendQueue};
+use signature_accumulator::SignatureAccumulator;
 use types::{MessageId, RoutingActionSender};
-use messages::{DirectMessage, HopMessage, Message, RequestContent, RequestMessage,
-               ResponseContent, ResponseMessage, RoutingMessage, SignedMessage};
+use messages::{BootstrapDenyReason, DirectMessage, GroupProof, HopContent, HopMessage, Message,
+               RequestContent, RequestMessage, ResponseContent, ResponseMessage, RoutingMessage,
+               SignedMessage, TracedHop};
+use resource_proof;
 use utils;
+use verifier::{Checked, CheckedContent, CheckedMessage, Verifier};
 
 const CRUST_DEFAULT_BEACON_PORT: u16 = 5484;
 const CRUST_DEFAULT_TCP_ACCEPTING_PORT: crust::Port = crust::Port::Tcp(5483);
@@ -55,6 +70,25 @@ const CRUST_DEFAULT_TCP_ACCEPTING_PORT: crust::Port = crust::Port::Tcp(5483);
 /// the same time.
 const MAX_JOINING_NODES: usize = 1;
 
+/// The interval between two `Tick` actions, which drive all timeouts.
+const TICK_INTERVAL_MS: u64 = 1000;
+
+/// The time after which a node that hasn't responded to a `FindNode` request is skipped.
+const LOOKUP_QUERY_TIMEOUT_SECS: i64 = 10;
+
+/// The time to wait before bootstrapping again after a proxy node refused us.
+const BOOTSTRAP_RETRY_DELAY_SECS: i64 = 5;
+
+/// The time for which we wait for the routes of the traced messages we sent.
+const ROUTE_TRACE_TIMEOUT_MINS: i64 = 10;
+
+/// The maximum number of traced messages we wait for the routes of.
+const MAX_TRACED_MESSAGES: usize = 1000;
+
+/// The token crust reports for bootstrap connections. Connections to nodes use the token of the
+/// current join attempt instead, so that connections for an abandoned attempt can be recognised.
+const BOOTSTRAP_TOKEN: u32 = 0;
+
 /// The state of the connection to the network.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
 enum State {
@@ -68,6 +102,18 @@ enum State {
     Node,
 }
 
+/// A client or joining node we are the proxy node for.
+struct ClientInfo {
+    connection: crust::Connection,
+    // Whether the client intends to remain a client, as opposed to becoming a routing node
+    client_restriction: bool,
+    rate_limiter: RateLimiter,
+    // The number of consecutive messages rejected because the client exceeded its rate limit
+    rate_limit_violations: usize,
+    // When the client last sent a message
+    last_activity: SteadyTime,
+}
+
 /// An interface for clients and nodes that handles routing and connecting to the network.
 ///
 ///
@@ -83,7 +129,9 @@ enum State {
 /// A now sends a `ClientIdentify` message to B, containing A's signed public ID. B verifies the
 /// signature and responds with a `BootstrapIdentify`, containing B's public ID and the current
 /// quorum size. Once it receives that, A goes into the `Client` state and uses B as its proxy to
-/// the network.
+/// the network. If A intends to become a node and B is already the proxy of too many joining nodes,
+/// B puts A in a queue instead, and informs it about its position with `BootstrapQueued` messages
+/// until it can accept A.
 ///
 /// A can now exchange messages with any `Authority`. This completes the bootstrap process for
 /// clients.
@@ -99,17 +147,29 @@ enum State {
 /// ### Getting a new network name from the `NaeManager`
 ///
 /// Once in `Client` state, A sends a `GetNetworkName` request to the `NaeManager` group authority X
-/// of A's current name. X computes a new name and sends it in its response to A.
+/// of A's current name. X computes a new name from A's current name and the two nodes closest to
+/// it, and sends it in its response to A, together with the names of those two nodes.
 ///
 /// It also sends an `ExpectCloseNode` request to the `NodeManager` Y of A's new name to inform Y
-/// about the new node. Each member of Y caches A's public ID.
+/// about the new node, again including A's original name and the two close nodes. Each member of
+/// Y recomputes the new name from these and, if it matches, caches A's public ID. A performs the
+/// same check before adopting its new name.
+///
+///
+/// ### Proving resources
+///
+/// A now sends a `GetCloseGroup` request to Y. Unless resource proofs are disabled in its
+/// `Config`, each member of Y individually responds with a `ResourceProofChallenge` containing a
+/// random seed. A has to send data derived from that seed, along with a nonce that it needs to
+/// search for, in a `ResourceProof` request to that member's `ManagedNode` authority. This makes
+/// it expensive to flood a part of the network with nodes.
 ///
 ///
 /// ### Connecting to the close group
 ///
-/// A now sends a `GetCloseGroup` request to Y. Each member of Y sends its own public ID and those
-/// of its close group in its response to A. Those messages don't necessarily agree, as not every
-/// member of Y has the same close group!
+/// Each member of Y that received a valid `ResourceProof` from A (or A's `GetCloseGroup` request,
+/// if resource proofs are disabled) sends its own public ID and those of its close group to A.
+/// Those messages don't necessarily agree, as not every member of Y has the same close group!
 ///
 /// To the `ManagedNode` for each public ID it receives from members of Y, A sends its `Endpoints`.
 /// It also caches the ID.
@@ -121,6 +181,9 @@ enum State {
 /// Once the connection between A and Z is established and a Crust `OnConnect` event is raised,
 /// they exchange `NodeIdentify` messages and add each other to their routing tables. When A
 /// receives its first `NodeIdentify`, it finally moves to the `Node` state.
+///
+/// If any of the above stages doesn't complete within the configured `join_timeout`, A raises a
+/// `JoinFailed` event, reverts to its original name and starts over by bootstrapping again.
 pub struct Core {
     // for CRUST
     crust_service: crust::Service,
@@ -129,32 +192,105 @@ pub struct Core {
     client_restriction: bool,
     is_listening: bool,
     crust_rx: mpsc::Receiver<crust::Event>,
+    // Decodes and checks incoming messages on worker threads
+    verifier: Verifier,
+    checked_rx: mpsc::Receiver<Checked>,
     action_rx: mpsc::Receiver<Action>,
     event_sender: mpsc::Sender<Event>,
-    signed_message_filter: MessageFilter<SignedMessage>,
+    // The digests of the signed messages we have handled
+    signed_message_filter: DigestFilter,
+    // The signatures of the traced messages we sent, whose routes we expect to be returned
+    traced_messages: LruCache<sign::Signature, ()>,
     connection_filter: MessageFilter<XorName>,
     node_id_cache: LruCache<XorName, PublicId>,
-    message_accumulator: Accumulator<RoutingMessage, sign::PublicKey>,
-    // Group messages which have been accumulated and then actioned
-    grp_msg_filter: MessageFilter<RoutingMessage>,
+    // The senders' IDs and signatures of the group messages being accumulated
+    message_accumulator: SignatureAccumulator,
+    // The digests of the group messages which have been accumulated and then actioned
+    grp_msg_filter: DigestFilter,
     full_id: FullId,
     state: State,
     routing_table: RoutingTable<PublicId, crust::Connection>,
     // our bootstrap connections
     proxy_map: HashMap<crust::Connection, PublicId>,
-    // any clients we have proxying through us, and whether they have `client_restriction`
-    client_map: HashMap<sign::PublicKey, (crust::Connection, bool)>,
+    // any clients we have proxying through us
+    client_map: HashMap<sign::PublicKey, ClientInfo>,
     data_cache: LruCache<XorName, Data>,
+    // Seeds of the resource proof challenges sent to joining nodes, and their relocated names
+    resource_proof_challenges: LruCache<sign::PublicKey, (Vec<u8>, XorName)>,
+    // The members of our new close group whose resource proof challenges we are solving
+    solving_resource_proofs: HashSet<XorName>,
+    // Senders for actions to ourselves, from the threads solving resource proofs
+    action_tx: mpsc::Sender<Action>,
+    category_tx: mpsc::Sender<MaidSafeEventCategory>,
+    // Joining nodes waiting for us to accept them as their proxy
+    join_queue: JoinQueue<crust::Connection, sign::PublicKey>,
+    // The stage our own join is in, and when it started
+    join_stage: Option<(JoinStage, SteadyTime)>,
+    // The token of the connections we make in the current join attempt
+    connect_token: u32,
+    // When to bootstrap again, after a proxy node refused us
+    bootstrap_retry_time: Option<SteadyTime>,
+    // The endpoints of the proxy nodes that refused us, which we don't bootstrap off again
+    bootstrap_blacklist: Vec<crust::Endpoint>,
+    // Connections of clients we evicted, which are dropped once our refusal has been sent
+    evicted_clients: HashSet<crust::Connection>,
+    lookups: HashMap<MessageId, Lookup>,
+    // IDs of the lookups we started to refresh our routing table
+    refresh_lookups: HashSet<MessageId>,
+    // When each bucket last gained a node or was refreshed
+    bucket_refresh_times: HashMap<usize, SteadyTime>,
+    network_size_estimate: u64,
+    liveness: Liveness<crust::Connection>,
+    // The scores of peers that violated the protocol, and the banned peers
+    reputation: Reputation<PeerId>,
+    banned_ips: BanList<IpAddr>,
+    banned_keys: BanList<sign::PublicKey>,
+    // Relayed messages not yet acknowledged by their next hops, with their destinations and
+    // priorities
+    pending_acks: PendingAcks<hash::sha512::Digest, XorName, (Vec<u8>, XorName, Priority)>,
+    // The messages waiting to be sent on each connection
+    send_queues: HashMap<crust::Connection, SendQueue>,
+    // The time from which the next tick flushes the send queues, if the byte rate held back some
+    // of their messages
+    send_flush_time: Option<SteadyTime>,
+    // The number of messages dropped because a send queue was full
+    send_queue_drops: u64,
+    // The number of messages dropped because they reached their hop limit
+    hop_limit_drops: u64,
+    config: Config,
 }
 
 impl Core {
     /// A Core instance for a client or node with the given id. Sends events to upper layer via the mpsc sender passed
     /// in.
-    pub fn new(event_sender: mpsc::Sender<Event>, client_restriction: bool, keys: Option<FullId>)
+    pub fn new(event_sender: mpsc::Sender<Event>,
+               client_restriction: bool,
+               keys: Option<FullId>,
+               config: Config)
                -> Result<(RoutingActionSender, RaiiThreadJoiner), RoutingError> {
+        try!(config.check());
         let (crust_tx, crust_rx) = mpsc::channel();
         let (action_tx, action_rx) = mpsc::channel();
         let (category_tx, category_rx) = mpsc::channel();
+        let (checked_tx, checked_rx) = mpsc::channel();
+
+        let full_id = match keys {
+            Some(full_id) => full_id,
+            None => FullId::new(),
+        };
+
+        let verifier = Verifier::new(config.verifier_threads,
+                                     config.max_verifier_queue_size,
+                                     full_id.encrypting_private_key().clone(),
+                                     checked_tx,
+                                     action_tx.clone(),
+                                     category_tx.clone());
+
+        let tick_sender = RoutingActionSender::new(action_tx.clone(),
+                                                   MaidSafeEventCategory::RoutingEvent,
+                                                   category_tx.clone());
+        let core_action_tx = action_tx.clone();
+        let core_category_tx = category_tx.clone();
 
         let routing_event_category = MaidSafeEventCategory::RoutingEvent;
         let action_sender = RoutingActionSender::new(action_tx,
@@ -171,11 +307,12 @@ impl Core {
             Err(what) => panic!(format!("Unable to start crust::Service {}", what)),
         };
 
-        let full_id = match keys {
-            Some(full_id) => full_id,
-            None => FullId::new(),
-        };
         let our_name = *full_id.public_id().name();
+        let heartbeat_interval = Duration::seconds(config.heartbeat_interval.as_secs() as i64);
+        let mut liveness = Liveness::new(heartbeat_interval, config.heartbeat_miss_threshold);
+        liveness.set_probe_active(config.latency_aware_forwarding);
+        let ban_duration = Duration::seconds(config.peer_ban_duration.as_secs() as i64);
+        let hop_ack_timeout = Duration::seconds(config.hop_ack_timeout.as_secs() as i64);
 
         let joiner = thread!("RoutingThread", move || {
             let mut core = Core {
@@ -184,39 +321,80 @@ impl Core {
                 client_restriction: client_restriction,
                 is_listening: false,
                 crust_rx: crust_rx,
+                verifier: verifier,
+                checked_rx: checked_rx,
                 action_rx: action_rx,
                 event_sender: event_sender,
-                signed_message_filter: MessageFilter::with_expiry_duration(Duration::minutes(20)),
+                signed_message_filter: message_filter(&config),
+                traced_messages: LruCache::with_expiry_duration_and_capacity(
+                    Duration::minutes(ROUTE_TRACE_TIMEOUT_MINS),
+                    MAX_TRACED_MESSAGES),
                 // TODO Needs further discussion on interval
                 connection_filter: MessageFilter::with_expiry_duration(Duration::seconds(20)), 
                 node_id_cache: LruCache::with_expiry_duration(Duration::minutes(10)),
-                message_accumulator: Accumulator::with_duration(1, Duration::minutes(5)),
-                grp_msg_filter: MessageFilter::with_expiry_duration(Duration::minutes(20)),
+                message_accumulator: message_accumulator(&config),
+                grp_msg_filter: message_filter(&config),
                 full_id: full_id,
                 state: State::Disconnected,
                 routing_table: RoutingTable::new(&our_name),
                 proxy_map: HashMap::new(),
                 client_map: HashMap::new(),
                 data_cache: LruCache::with_expiry_duration(Duration::minutes(10)),
+                resource_proof_challenges: LruCache::with_expiry_duration(Duration::minutes(5)),
+                solving_resource_proofs: HashSet::new(),
+                action_tx: core_action_tx,
+                category_tx: core_category_tx,
+                join_queue: JoinQueue::new(config.max_join_queue_len),
+                join_stage: None,
+                connect_token: BOOTSTRAP_TOKEN + 1,
+                bootstrap_retry_time: None,
+                bootstrap_blacklist: Vec::new(),
+                evicted_clients: HashSet::new(),
+                lookups: HashMap::new(),
+                refresh_lookups: HashSet::new(),
+                bucket_refresh_times: HashMap::new(),
+                network_size_estimate: 1,
+                liveness: liveness,
+                reputation: Reputation::new(config.peer_drop_score,
+                                            config.peer_ban_score,
+                                            ban_duration),
+                banned_ips: BanList::new(ban_duration),
+                banned_keys: BanList::new(ban_duration),
+                pending_acks: PendingAcks::new(hop_ack_timeout, config.max_pending_acks),
+                send_queues: HashMap::new(),
+                send_flush_time: None,
+                send_queue_drops: 0,
+                hop_limit_drops: 0,
+                config: config,
             };
 
             core.run(category_rx);
         });
 
+        // Runs until the `Core` has terminated and the channel is closed.
+        let _ = thread!("RoutingTimer", move || {
+            loop {
+                thread::sleep(::std::time::Duration::from_millis(TICK_INTERVAL_MS));
+                if tick_sender.send(Action::Tick).is_err() {
+                    break;
+                }
+            }
+        });
+
         Ok((action_sender, RaiiThreadJoiner::new(joiner)))
     }
 
     /// Run the event loop for sending and receiving messages.
     pub fn run(&mut self, category_rx: mpsc::Receiver<MaidSafeEventCategory>) {
         let mut cur_routing_table_size = 0;
-        self.crust_service.bootstrap(0u32, Some(CRUST_DEFAULT_BEACON_PORT));
+        self.crust_service.bootstrap(BOOTSTRAP_TOKEN, Some(CRUST_DEFAULT_BEACON_PORT));
         for it in category_rx.iter() {
             match it {
                 MaidSafeEventCategory::RoutingEvent => {
                     if let Ok(action) = self.action_rx.try_recv() {
                         match action {
-                            Action::NodeSendMessage { content, result_tx, } => {
-                                match self.send_message(content) {
+                            Action::NodeSendMessage { content, trace_route, result_tx, } => {
+                                match self.send_message(content, trace_route) {
                                     Err(RoutingError::Interface(err)) => {
                                         if result_tx.send(Err(err)).is_err() {
                                             return;
@@ -234,7 +412,7 @@ impl Core {
                                     }
                                 }
                             }
-                            Action::ClientSendRequest { content, dst, result_tx, } => {
+                            Action::ClientSendRequest { content, dst, trace_route, result_tx, } => {
                                 if let Ok(src) = self.get_client_authority() {
                                     let request_msg = RequestMessage {
                                         content: content,
@@ -243,7 +421,7 @@ impl Core {
                                     };
 
                                     let routing_msg = RoutingMessage::Request(request_msg);
-                                    match self.send_message(routing_msg) {
+                                    match self.send_message(routing_msg, trace_route) {
                                         Err(RoutingError::Interface(err)) => {
                                             if result_tx.send(Err(err)).is_err() {
                                                 return;
@@ -278,6 +456,79 @@ impl Core {
                                     return;
                                 }
                             }
+                            Action::ClosestNodes{ name, count, result_tx, } => {
+                                if result_tx.send(self.closest_known_nodes(&name, count))
+                                            .is_err() {
+                                    return;
+                                }
+                            }
+                            Action::IsResponsibleFor{ name, result_tx, } => {
+                                let group_size = kademlia_routing_table::group_size();
+                                let is_responsible = self.closest_known_nodes(&name, group_size)
+                                                         .contains(self.full_id
+                                                                       .public_id()
+                                                                       .name());
+                                if result_tx.send(is_responsible).is_err() {
+                                    return;
+                                }
+                            }
+                            Action::FindNodes{ target, id, result_tx, } => {
+                                let result = if self.state == State::Node {
+                                    self.start_lookup(target, id);
+                                    Ok(())
+                                } else {
+                                    Err(InterfaceError::NotConnected)
+                                };
+                                if result_tx.send(result).is_err() {
+                                    return;
+                                }
+                            }
+                            Action::NetworkSize{ result_tx, } => {
+                                if result_tx.send(self.network_size_estimate).is_err() {
+                                    return;
+                                }
+                            }
+                            Action::RoutingTable{ result_tx, } => {
+                                if result_tx.send(self.routing_table_snapshot()).is_err() {
+                                    return;
+                                }
+                            }
+                            Action::Tick => self.handle_tick(),
+                            Action::MessageChecked => {
+                                match self.checked_rx.try_recv() {
+                                    Ok(Checked::Message(checked_msg)) => {
+                                        match self.handle_checked_message(checked_msg) {
+                                            Err(RoutingError::FilterCheckFailed) => (),
+                                            Err(err) => error!("{:?} {:?}", self, err),
+                                            Ok(_) => (),
+                                        }
+                                    }
+                                    Ok(Checked::Endpoints(token, Ok(endpoints))) => {
+                                        self.connect_to_endpoints(token, endpoints)
+                                    }
+                                    Ok(Checked::Endpoints(_, Err(err))) => {
+                                        error!("{:?} Failed to decrypt endpoints - {:?}",
+                                               self,
+                                               err)
+                                    }
+                                    Err(_) => (),
+                                }
+                            }
+                            Action::ResourceProofSolved { seed,
+                                                          data,
+                                                          nonce,
+                                                          member_name,
+                                                          client_key,
+                                                          proxy_name } => {
+                                if let Err(err) = self.send_resource_proof(seed,
+                                                                           data,
+                                                                           nonce,
+                                                                           member_name,
+                                                                           client_key,
+                                                                           proxy_name) {
+                                    error!("{:?} {:?}", self, err);
+                                }
+                            }
                             Action::Terminate => {
                                 break;
                             }
@@ -296,10 +547,8 @@ impl Core {
                                 self.handle_lost_connection(connection)
                             }
                             crust::Event::NewMessage(connection, bytes) => {
-                                match self.handle_new_message(connection, bytes) {
-                                    Err(RoutingError::FilterCheckFailed) => (),
-                                    Err(err) => error!("{:?} {:?}", self, err),
-                                    Ok(_) => (),
+                                if let Err(err) = self.handle_new_message(connection, bytes) {
+                                    error!("{:?} {:?}", self, err);
                                 }
                             }
                             crust::Event::OnConnect(io_result, connection_token) => {
@@ -322,6 +571,9 @@ impl Core {
 
             if self.state == State::Node && cur_routing_table_size != self.routing_table.len() {
                 cur_routing_table_size = self.routing_table.len();
+                self.network_size_estimate =
+                    utils::estimate_network_size(self.full_id.public_id().name(),
+                                                 &self.close_group_names());
                 trace!(" -----------------------------------");
                 trace!("| Routing Table size updated to: {}",
                        self.routing_table.len());
@@ -334,55 +586,783 @@ impl Core {
         } // Category Rx
     }
 
+    // Starts a lookup for the group of nodes closest to `target`, with ourselves as the first node
+    // that has responded.
+    fn start_lookup(&mut self, target: XorName, id: MessageId) {
+        let count = kademlia_routing_table::group_size();
+        let close_nodes = self.routing_table
+                              .closest_nodes_to(&target, count, false)
+                              .into_iter()
+                              .map(|node_info| node_info.public_id)
+                              .collect_vec();
+        let mut lookup = Lookup::new(target, count, vec![]);
+        lookup.add_response(self.full_id.public_id().clone(), close_nodes);
+        if self.lookups.insert(id.clone(), lookup).is_some() {
+            warn!("{:?} Replaced running lookup {:?}.", self, id);
+        }
+        self.continue_lookup(id);
+    }
+
+    // Sends the lookup's next queries, or raises an event if it is complete.
+    fn continue_lookup(&mut self, id: MessageId) {
+        let (target, queries, is_complete) = match self.lookups.get_mut(&id) {
+            Some(lookup) => {
+                let queries = lookup.next_queries(SteadyTime::now());
+                (*lookup.target(), queries, lookup.is_complete())
+            }
+            None => return,
+        };
+
+        for public_id in queries {
+            if let Err(error) = self.send_find_node_request(target, id.clone(), *public_id.name()) {
+                debug!("{:?} Failed to send FindNode request to {:?}: {:?}",
+                       self,
+                       public_id.name(),
+                       error);
+            }
+        }
+
+        if is_complete {
+            if let Some(lookup) = self.lookups.remove(&id) {
+                if self.refresh_lookups.remove(&id) {
+                    // Connect to the nodes we found that would improve our routing table.
+                    for public_id in lookup.unverified_result() {
+                        if self.connection_filter.insert(*public_id.name()).is_none() &&
+                           self.routing_table.want_to_add(public_id.name()) {
+                            let _ = self.send_connect_request(public_id.name());
+                        }
+                    }
+                } else {
+                    let _ = self.event_sender.send(Event::LookupComplete {
+                        id: id,
+                        target: target,
+                        unverified_nodes: lookup.unverified_result(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn send_find_node_request(&mut self,
+                              target: XorName,
+                              id: MessageId,
+                              dst_name: XorName)
+                              -> Result<(), RoutingError> {
+        let request_msg = RequestMessage {
+            src: Authority::ManagedNode(*self.full_id.public_id().name()),
+            dst: Authority::ManagedNode(dst_name),
+            content: RequestContent::FindNode {
+                target: target,
+                id: id,
+            },
+        };
+
+        let routing_msg = RoutingMessage::Request(request_msg);
+
+        let signed_msg = try!(SignedMessage::new(routing_msg, &self.full_id));
+
+        self.send(signed_msg)
+    }
+
+    fn handle_find_node_request(&mut self,
+                                target: XorName,
+                                id: MessageId,
+                                src_name: XorName,
+                                dst_name: XorName)
+                                -> Result<(), RoutingError> {
+        let mut close_nodes = self.routing_table
+                                  .closest_nodes_to(&target,
+                                                    kademlia_routing_table::group_size(),
+                                                    false)
+                                  .into_iter()
+                                  .map(|node_info| node_info.public_id)
+                                  .collect_vec();
+        close_nodes.push(self.full_id.public_id().clone());
+
+        let response_msg = ResponseMessage {
+            src: Authority::ManagedNode(dst_name),
+            dst: Authority::ManagedNode(src_name),
+            content: ResponseContent::FindNode {
+                target: target,
+                id: id,
+                close_nodes: close_nodes,
+            },
+        };
+
+        let routing_msg = RoutingMessage::Response(response_msg);
+
+        let signed_msg = try!(SignedMessage::new(routing_msg, &self.full_id));
+
+        self.send(signed_msg)
+    }
+
+    fn handle_get_group_ids_request(&mut self,
+                                    id: MessageId,
+                                    src: Authority,
+                                    dst: Authority)
+                                    -> Result<(), RoutingError> {
+        if !dst.is_group() {
+            return Err(RoutingError::BadAuthority);
+        }
+        let group_ids = self.closest_known_ids(dst.get_name(),
+                                               kademlia_routing_table::group_size());
+
+        let response_msg = ResponseMessage {
+            src: dst,
+            dst: src,
+            content: ResponseContent::GetGroupIds {
+                id: id,
+                group_ids: group_ids,
+            },
+        };
+
+        let routing_msg = RoutingMessage::Response(response_msg);
+
+        let signed_msg = try!(SignedMessage::new(routing_msg, &self.full_id));
+
+        self.send(signed_msg)
+    }
+
+    fn handle_find_node_response(&mut self,
+                                 signer: &PublicId,
+                                 src_name: &XorName,
+                                 id: &MessageId,
+                                 close_nodes: Vec<PublicId>)
+                                 -> Result<(), RoutingError> {
+        if signer.name() != src_name {
+            return Err(RoutingError::BadAuthority);
+        }
+        match self.lookups.get_mut(id) {
+            Some(lookup) => {
+                if !lookup.handle_response(signer, close_nodes) {
+                    // Late, or from a node we didn't query.
+                    debug!("{:?} Ignoring unexpected FindNode response from {:?}.",
+                           self,
+                           signer);
+                    return Ok(());
+                }
+            }
+            None => return Ok(()),
+        }
+        self.continue_lookup(id.clone());
+        Ok(())
+    }
+
+    // Returns the route of a traced message we received to its source authority.
+    fn send_route_trace(&mut self,
+                        signed_msg: &SignedMessage,
+                        mut route: Vec<TracedHop>)
+                        -> Result<(), RoutingError> {
+        let hop = try!(TracedHop::new(signed_msg, route.last(), &self.full_id));
+        route.push(hop);
+        let response = ResponseMessage {
+            src: Authority::ManagedNode(*self.full_id.public_id().name()),
+            dst: signed_msg.content().src().clone(),
+            content: ResponseContent::RouteTrace {
+                message_signature: signed_msg.signature().clone(),
+                id: signed_msg.content().message_id().cloned(),
+                dst: signed_msg.content().dst().clone(),
+                route: route,
+            },
+        };
+        let signed_response = try!(SignedMessage::new(RoutingMessage::Response(response),
+                                                      &self.full_id));
+        self.send(signed_response)
+    }
+
+    fn handle_route_trace(&mut self,
+                          message_signature: sign::Signature,
+                          id: Option<MessageId>,
+                          dst: Authority,
+                          route: Vec<TracedHop>)
+                          -> Result<(), RoutingError> {
+        if self.traced_messages.remove(&message_signature).is_none() {
+            debug!("{:?} Received the route of a message we didn't trace.", self);
+            return Ok(());
+        }
+        try!(TracedHop::verify_route(&message_signature, &route));
+        let event = Event::RouteTraced {
+            id: id,
+            dst: dst,
+            route: route.iter().map(|hop| *hop.public_id().name()).collect(),
+        };
+        if let Err(err) = self.event_sender.send(event) {
+            error!("Error sending event to routing user - {:?}", err);
+        }
+        Ok(())
+    }
+
+    // The names of the `count` nodes closest to `name` in our routing table, including ourselves
+    // if we are a node.
+    fn closest_known_nodes(&self, name: &XorName, count: usize) -> Vec<XorName> {
+        self.closest_known_ids(name, count)
+            .into_iter()
+            .map(|public_id| *public_id.name())
+            .collect()
+    }
+
+    // The `PublicId`s of the `count` nodes closest to `name` in our routing table, including
+    // ourselves if we are a node.
+    fn closest_known_ids(&self, name: &XorName, count: usize) -> Vec<PublicId> {
+        let mut public_ids = self.routing_table
+                                 .closest_nodes_to(name, count, false)
+                                 .into_iter()
+                                 .map(|node_info| node_info.public_id)
+                                 .collect_vec();
+        if self.state == State::Node {
+            public_ids.push(self.full_id.public_id().clone());
+        }
+        public_ids.sort_by(|lhs, rhs| {
+            if ::xor_name::closer_to_target(lhs.name(), rhs.name(), name) {
+                ::std::cmp::Ordering::Less
+            } else {
+                ::std::cmp::Ordering::Greater
+            }
+        });
+        public_ids.truncate(count);
+        public_ids
+    }
+
+    fn routing_table_snapshot(&self) -> RoutingTableSnapshot {
+        let our_name = *self.full_id.public_id().name();
+        let close_group = self.close_group_names();
+        let entries = self.routing_table
+                          .closest_nodes_to(&our_name, self.routing_table.len(), false)
+                          .into_iter()
+                          .map(|node_info| {
+                              let round_trip_time =
+                                  self.node_round_trip_time(&node_info).map(|rtt| {
+                                      ::std::time::Duration::from_millis(rtt.num_milliseconds()
+                                                                             as u64)
+                                  });
+                              RoutingTableEntry {
+                                  bucket_index: utils::bucket_index(&our_name,
+                                                                    node_info.public_id.name()),
+                                  connections: node_info.connections.len(),
+                                  is_close: close_group.contains(node_info.public_id.name()),
+                                  round_trip_time: round_trip_time,
+                                  public_id: node_info.public_id,
+                              }
+                          })
+                          .collect();
+        let state = match self.state {
+            State::Disconnected => ConnectionState::Disconnected,
+            State::Bootstrapping => ConnectionState::Bootstrapping,
+            State::Client => ConnectionState::Client,
+            State::Node => ConnectionState::Node,
+        };
+        let joining_node_count = self.joining_nodes_num();
+        RoutingTableSnapshot {
+            our_name: our_name,
+            state: state,
+            entries: entries,
+            proxy_count: self.proxy_map.len(),
+            client_count: self.client_map.len() - joining_node_count,
+            joining_node_count: joining_node_count,
+            queued_joining_node_count: self.join_queue.len(),
+            network_size_estimate: self.network_size_estimate,
+            hop_limit_drops: self.hop_limit_drops,
+            send_queue_drops: self.send_queue_drops,
+            message_filter_evictions: self.signed_message_filter.early_evictions() +
+                                      self.grp_msg_filter.early_evictions(),
+            accumulator_evictions: self.message_accumulator.early_evictions(),
+        }
+    }
+
+    fn handle_tick(&mut self) {
+        if let Some((stage, start_time)) = self.join_stage {
+            let timeout = Duration::seconds(self.config.join_timeout.as_secs() as i64);
+            if SteadyTime::now() - start_time > timeout {
+                self.restart_join(stage);
+            }
+        }
+
+        let now = SteadyTime::now();
+        if self.bootstrap_retry_time.map_or(false, |retry_time| now >= retry_time) {
+            self.restart_bootstrap();
+        }
+
+        let lookup_ids = self.lookups.keys().cloned().collect_vec();
+        for id in lookup_ids {
+            if let Some(lookup) = self.lookups.get_mut(&id) {
+                lookup.expire_queries(now, Duration::seconds(LOOKUP_QUERY_TIMEOUT_SECS));
+            }
+            self.continue_lookup(id);
+        }
+
+        self.refresh_stale_buckets();
+        self.check_liveness();
+        self.resend_unacknowledged();
+        // Evicted clients are dropped before flushing, so their refusal has been passed to Crust
+        // in an earlier tick.
+        self.drop_evicted_clients();
+        if self.send_flush_time.map_or(false, |flush_time| flush_time <= now) {
+            self.send_flush_time = None;
+            self.flush_send_queues();
+        }
+    }
+
+    // Drops the connections of evicted clients whose refusal has been sent.
+    fn drop_evicted_clients(&mut self) {
+        let connections = self.evicted_clients
+                              .iter()
+                              .filter(|connection| {
+                                  self.send_queues
+                                      .get(connection)
+                                      .map_or(true, |queue| queue.is_empty())
+                              })
+                              .cloned()
+                              .collect_vec();
+        for connection in connections {
+            self.crust_service.drop_node(connection);
+            self.handle_lost_connection(connection);
+        }
+    }
+
+    // Queues the message for sending on the connection, and sends as much of the queue as the
+    // byte rate allows.
+    fn send_bytes(&mut self,
+                  connection: crust::Connection,
+                  priority: Priority,
+                  raw_bytes: Vec<u8>)
+                  -> Result<(), RoutingError> {
+        let now = SteadyTime::now();
+        let max_len = self.config.max_send_queue_size;
+        let byte_rate = self.config.send_byte_rate;
+        let burst = Duration::seconds(self.config.send_rate_burst.as_secs() as i64);
+        let queued = self.send_queues
+                         .entry(connection)
+                         .or_insert_with(|| SendQueue::new(max_len, byte_rate, burst, now))
+                         .push(priority, raw_bytes);
+        self.flush_send_queue(connection, now);
+        if queued {
+            Ok(())
+        } else {
+            self.send_queue_drops += 1;
+            debug!("{:?} Send queue full on {:?} - Refusing {:?} message",
+                   self,
+                   connection,
+                   priority);
+            Err(RoutingError::Interface(InterfaceError::SendQueueFull))
+        }
+    }
+
+    // Sends as much of the queue as the byte rate allows, and schedules a flush for when the rest
+    // can be sent.
+    fn flush_send_queue(&mut self, connection: crust::Connection, now: SteadyTime) {
+        let wait = match self.send_queues.get_mut(&connection) {
+            Some(queue) => {
+                while let Some(raw_bytes) = queue.pop(now) {
+                    self.crust_service.send(connection, raw_bytes);
+                }
+                queue.time_until_next(now)
+            }
+            None => None,
+        };
+        if let Some(wait) = wait {
+            self.schedule_send_flush(now + wait);
+        }
+    }
+
+    // Makes sure the send queues are flushed on the first tick at or after `flush_time`.
+    fn schedule_send_flush(&mut self, flush_time: SteadyTime) {
+        if self.send_flush_time.map_or(true, |scheduled| flush_time < scheduled) {
+            self.send_flush_time = Some(flush_time);
+        }
+    }
+
+    fn flush_send_queues(&mut self) {
+        let now = SteadyTime::now();
+        let connections = self.send_queues
+                              .iter()
+                              .filter(|&(_, queue)| !queue.is_empty())
+                              .map(|(connection, _)| *connection)
+                              .collect_vec();
+        for connection in connections {
+            self.flush_send_queue(connection, now);
+        }
+    }
+
+    // Sends heartbeats to quiet routing table connections, or to all of them if latency-aware
+    // forwarding is enabled, and drops the unresponsive ones.
+    fn check_liveness(&mut self) {
+        let connections = self.routing_table
+                              .closest_nodes_to(self.full_id.public_id().name(),
+                                                self.routing_table.len(),
+                                                false)
+                              .into_iter()
+                              .flat_map(|node_info| node_info.connections.into_iter())
+                              .collect_vec();
+        let (probes, dead) = self.liveness.check(connections, SteadyTime::now());
+        for connection in probes {
+            let _ = self.send_direct_message(connection, DirectMessage::Heartbeat);
+        }
+        for connection in dead {
+            warn!("{:?} No response to heartbeats - Dropping connection {:?}",
+                  self,
+                  connection);
+            self.crust_service.drop_node(connection);
+            self.handle_lost_connection(connection);
+        }
+    }
+
+    fn send_direct_message(&mut self,
+                           connection: crust::Connection,
+                           direct_message: DirectMessage)
+                           -> Result<(), RoutingError> {
+        let message = Message::DirectMessage(direct_message);
+        let raw_bytes = try!(serialisation::serialise(&message));
+        self.send_bytes(connection, Priority::Control, raw_bytes)
+    }
+
+    // Looks up a random name in each bucket that hasn't gained any nodes or been refreshed within
+    // the refresh interval, so that we learn about the nodes in its range.
+    fn refresh_stale_buckets(&mut self) {
+        if self.state != State::Node {
+            return;
+        }
+        let our_name = *self.full_id.public_id().name();
+        // Buckets beyond the one our closest node is in are necessarily empty.
+        let deepest_index = match self.routing_table.closest_nodes_to(&our_name, 1, false).first() {
+            Some(node_info) => utils::bucket_index(&our_name, node_info.public_id.name()),
+            None => return,
+        };
+        let interval = Duration::seconds(self.config.bucket_refresh_interval.as_secs() as i64);
+        let now = SteadyTime::now();
+        for index in 0..(deepest_index + 1) {
+            let is_stale = self.bucket_refresh_times
+                               .get(&index)
+                               .map_or(true, |refresh_time| now - *refresh_time > interval);
+            if is_stale {
+                trace!("{:?} Refreshing bucket {}.", self, index);
+                let _ = self.bucket_refresh_times.insert(index, now);
+                let id = MessageId::new();
+                let _ = self.refresh_lookups.insert(id.clone());
+                self.start_lookup(utils::random_name_in_bucket(&our_name, index), id);
+            }
+        }
+    }
+
+    fn set_join_stage(&mut self, stage: JoinStage) {
+        trace!("{:?} Join stage: {:?}", self, stage);
+        self.join_stage = Some((stage, SteadyTime::now()));
+    }
+
+    fn is_join_stage(&self, stage: JoinStage) -> bool {
+        self.join_stage.map_or(false, |(current_stage, _)| current_stage == stage)
+    }
+
+    // Give up on the current attempt to join, and start over with a fresh bootstrap.
+    fn restart_join(&mut self, stage: JoinStage) {
+        warn!("{:?} Join stalled at {:?}. Restarting.", self, stage);
+        let _ = self.event_sender.send(Event::JoinFailed(stage));
+        self.join_stage = None;
+        self.solving_resource_proofs.clear();
+
+        // Drop the connections to our abandoned close group, forget their IDs and ignore any
+        // connections to them that crust still establishes.
+        let our_name = *self.full_id.public_id().name();
+        let connections = self.routing_table
+                              .closest_nodes_to(&our_name, self.routing_table.len(), false)
+                              .into_iter()
+                              .flat_map(|node_info| node_info.connections.into_iter())
+                              .collect_vec();
+        for connection in connections {
+            self.drop_connection(connection);
+        }
+        self.node_id_cache = LruCache::with_expiry_duration(Duration::minutes(10));
+        self.connection_filter = MessageFilter::with_expiry_duration(Duration::seconds(20));
+        self.connect_token = self.connect_token.wrapping_add(1);
+        if self.connect_token == BOOTSTRAP_TOKEN {
+            self.connect_token += 1;
+        }
+
+        // Revert to our original name, so that we can be relocated again.
+        let original_name =
+            XorName(hash::sha512::hash(&self.full_id.public_id().signing_public_key().0).0);
+        self.full_id.public_id_mut().set_name(original_name);
+        self.routing_table = RoutingTable::new(&original_name);
+
+        self.crust_service.stop_bootstrap();
+        self.restart_bootstrap();
+    }
+
     fn handle_new_message(&mut self,
                           connection: crust::Connection,
                           bytes: Vec<u8>)
                           -> Result<(), RoutingError> {
-        match serialisation::deserialise(&bytes) {
-            Ok(Message::HopMessage(ref hop_msg)) => self.handle_hop_message(hop_msg, connection),
-            Ok(Message::DirectMessage(direct_msg)) => {
+        self.liveness.heard_from(&connection, SteadyTime::now());
+        // Don't deserialise messages that are too large for any kind of message.
+        if bytes.len() > cmp::max(self.config.max_hop_message_size,
+                                  self.config.max_direct_message_size) {
+            self.penalise(connection, Violation::OversizedMessage);
+            return Err(RoutingError::MessageTooLarge);
+        }
+        if let Err(error) = self.verifier.check(connection, bytes) {
+            self.penalise(connection, Violation::Flooding);
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    // Handles a message once the verifier has decoded it and checked its signatures.
+    fn handle_checked_message(&mut self, checked_msg: CheckedMessage) -> Result<(), RoutingError> {
+        let CheckedMessage { connection, size, content } = checked_msg;
+        self.verifier.checked(&connection, size);
+        // The connection has been lost or dropped since the message was received.
+        if !self.verifier.has_connection(&connection) {
+            return Ok(());
+        }
+        match content {
+            Ok(CheckedContent::Hop(hop_msg, signed_msg, verified_key)) => {
+                if size > self.config.max_hop_message_size {
+                    self.penalise(connection, Violation::OversizedMessage);
+                    return Err(RoutingError::MessageTooLarge);
+                }
+                self.handle_hop_message(&hop_msg, signed_msg, verified_key, connection, size)
+            }
+            Ok(CheckedContent::Direct(direct_msg)) => {
+                if size > self.config.max_direct_message_size {
+                    self.penalise(connection, Violation::OversizedMessage);
+                    return Err(RoutingError::MessageTooLarge);
+                }
                 self.handle_direct_message(direct_msg, connection)
             }
-            Err(error) => Err(RoutingError::SerialisationError(error)),
+            Ok(CheckedContent::Identify(direct_msg, public_id)) => {
+                if size > self.config.max_direct_message_size {
+                    self.penalise(connection, Violation::OversizedMessage);
+                    return Err(RoutingError::MessageTooLarge);
+                }
+                self.handle_identify(direct_msg, public_id, connection)
+            }
+            Err(RoutingError::FailedSignature) => {
+                self.penalise(connection, Violation::InvalidSignature);
+                Err(RoutingError::FailedSignature)
+            }
+            Err(error) => {
+                self.penalise(connection, Violation::UndecodableMessage);
+                Err(error)
+            }
         }
     }
 
+    // Handles a relayed message whose content and originator's signature have been checked.
+    // `verified_key` is the key its hop signature has been verified against, if any, and `size` is
+    // the number of bytes received.
     fn handle_hop_message(&mut self,
                           hop_msg: &HopMessage,
-                          connection: crust::Connection)
+                          signed_msg: SignedMessage,
+                          verified_key: Option<sign::PublicKey>,
+                          connection: crust::Connection,
+                          size: usize)
                           -> Result<(), RoutingError> {
-        if self.state == State::Node {
-            if let Some(&NodeInfo { ref public_id, ..}) = self.routing_table.get(hop_msg.name()) {
-                try!(hop_msg.verify(public_id.signing_public_key()));
-            } else if let Some((ref pub_key, _)) = self.client_map
-                                                       .iter()
-                                                       .find(|ref elt| connection == (elt.1).0) {
-                try!(hop_msg.verify(pub_key));
+        let client_key = if self.state == State::Node {
+            self.client_map
+                .iter()
+                .find(|elt| connection == elt.1.connection)
+                .map(|elt| *elt.0)
+        } else {
+            None
+        };
+        let verification_key = if self.state == State::Node {
+            if let Some(node_info) = self.routing_table.get(hop_msg.name()) {
+                Some(*node_info.public_id.signing_public_key())
+            } else if client_key.is_some() {
+                client_key
             } else {
-                // TODO drop connection ?
+                // This can legitimately happen while the peer is joining or after churn, so the
+                // message is dropped without penalising the peer.
                 return Err(RoutingError::UnknownConnection);
             }
         } else if self.state == State::Client {
-            if let Some(pub_id) = self.proxy_map.get(&connection) {
-                try!(hop_msg.verify(pub_id.signing_public_key()));
-            }
+            self.proxy_map.get(&connection).map(|pub_id| *pub_id.signing_public_key())
         } else {
             return Err(RoutingError::InvalidStateForOperation);
+        };
+
+        // The verifier only knows the key if the peer was identified before the message was
+        // queued. Otherwise, the hop signature is verified here.
+        let peer_key = match verification_key {
+            Some(verification_key) => verification_key,
+            None => return Err(RoutingError::UnknownConnection),
+        };
+        if verified_key != Some(peer_key) {
+            if let Err(error) = hop_msg.verify(&peer_key) {
+                if let RoutingError::FailedSignature = error {
+                    self.penalise(connection, Violation::InvalidSignature);
+                }
+                return Err(error);
+            }
+        }
+
+        if self.state == State::Node {
+            try!(self.check_trace(&signed_msg, hop_msg.name(), hop_msg.trace()));
         }
 
-        self.handle_signed_message(hop_msg.content().clone(), hop_msg.name().clone())
+        if hop_msg.ack_requested() {
+            let digest = *hop_msg.content().digest();
+            let _ = self.send_direct_message(connection, DirectMessage::HopAck { digest: digest });
+        }
+
+        if let Some(client_key) = client_key {
+            try!(self.check_client_message(&client_key, signed_msg.content().src(), size));
+        }
+
+        if let Err(RoutingError::Interface(InterfaceError::DataTooLarge { size, max_size })) =
+               self.check_data_size(signed_msg.content()) {
+            debug!("{:?} Received data of size {}, maximum is {}.", self, size, max_size);
+            self.penalise(connection, Violation::OversizedMessage);
+            return Err(RoutingError::MessageTooLarge);
+        }
+
+        match self.handle_signed_message(signed_msg,
+                                         hop_msg.content(),
+                                         hop_msg.name().clone(),
+                                         peer_key,
+                                         hop_msg.hops_remaining(),
+                                         &hop_msg.trace().cloned()) {
+            Err(RoutingError::FailedSignature) => {
+                self.penalise(connection, Violation::InvalidSignature);
+                Err(RoutingError::FailedSignature)
+            }
+            result => result,
+        }
     }
 
+    // Checks that a message's route is recorded if and only if its originator asked for it, and
+    // that the last hop on the route is the node we received the message from. Otherwise, relays
+    // could remove the route or hops from it without being recorded themselves.
+    fn check_trace(&self,
+                   signed_msg: &SignedMessage,
+                   hop_name: &XorName,
+                   trace: Option<&Vec<TracedHop>>)
+                   -> Result<(), RoutingError> {
+        let route = match trace {
+            Some(route) if signed_msg.trace_route() => route,
+            None if !signed_msg.trace_route() => return Ok(()),
+            _ => return Err(RoutingError::InvalidTrace),
+        };
+        // Clients are not part of the route, so it is empty if we received it from a client.
+        let sender = match route.len() {
+            0 => *signed_msg.public_id().name(),
+            len => {
+                let previous = if len > 1 {
+                    Some(&route[len - 2])
+                } else {
+                    None
+                };
+                try!(route[len - 1].verify(signed_msg.signature(), previous));
+                *route[len - 1].public_id().name()
+            }
+        };
+        if sender == *hop_name {
+            Ok(())
+        } else {
+            Err(RoutingError::InvalidTrace)
+        }
+    }
+
+    // Decreases the peer's score, and drops or bans it if the score gets too low.
+    fn penalise(&mut self, connection: crust::Connection, violation: Violation) {
+        let endpoint = connection.peer_endpoint();
+        let ip = utils::ip_from_socketaddr(endpoint.get_address());
+        let public_key = self.peer_public_key(&connection);
+        let peer_id = public_key.map_or(PeerId::Ip(ip), PeerId::Key);
+        let now = SteadyTime::now();
+        match self.reputation.penalise(peer_id, violation, now) {
+            Verdict::Keep => return,
+            Verdict::Drop => {
+                warn!("{:?} Protocol violation {:?} - Dropping connection {:?}",
+                      self,
+                      violation,
+                      connection);
+            }
+            Verdict::Ban => {
+                warn!("{:?} Protocol violation {:?} - Banning peer {:?} on connection {:?}",
+                      self,
+                      violation,
+                      public_key,
+                      connection);
+                self.banned_ips.ban(ip, now);
+                if let Some(public_key) = public_key {
+                    self.banned_keys.ban(public_key, now);
+                }
+                let event = Event::PeerBanned {
+                    public_key: public_key,
+                    endpoint: endpoint,
+                };
+                if let Err(err) = self.event_sender.send(event) {
+                    error!("Error sending event to routing user - {:?}", err);
+                }
+            }
+        }
+        self.crust_service.drop_node(connection);
+        self.handle_lost_connection(connection);
+    }
+
+    // Returns the routing table entry of the node on the connection.
+    fn routing_node_on(&self,
+                       connection: &crust::Connection)
+                       -> Option<NodeInfo<PublicId, crust::Connection>> {
+        self.routing_table
+            .closest_nodes_to(self.full_id.public_id().name(),
+                              self.routing_table.len(),
+                              false)
+            .into_iter()
+            .find(|node_info| node_info.connections.contains(connection))
+    }
+
+    // Returns the signing key of the routing node, client or proxy on the connection.
+    fn peer_public_key(&self, connection: &crust::Connection) -> Option<sign::PublicKey> {
+        if let Some(node_info) = self.routing_node_on(connection) {
+            return Some(*node_info.public_id.signing_public_key());
+        }
+        if let Some((pub_key, _)) = self.client_map
+                                        .iter()
+                                        .find(|elt| *connection == elt.1.connection) {
+            return Some(*pub_key);
+        }
+        self.proxy_map.get(connection).map(|pub_id| *pub_id.signing_public_key())
+    }
+
+    // Drops the connection if the peer's IP address is banned.
+    fn drop_if_banned_ip(&mut self, connection: crust::Connection) -> bool {
+        let ip = utils::ip_from_socketaddr(connection.peer_endpoint().get_address());
+        if !self.banned_ips.is_banned(&ip, SteadyTime::now()) {
+            return false;
+        }
+        debug!("{:?} Refusing connection {:?} from banned IP address", self, connection);
+        self.drop_connection(connection);
+        true
+    }
+
+    // Drops the connection if the peer's key is banned.
+    fn drop_if_banned_key(&mut self,
+                          connection: crust::Connection,
+                          public_key: &sign::PublicKey)
+                          -> bool {
+        if !self.banned_keys.is_banned(public_key, SteadyTime::now()) {
+            return false;
+        }
+        debug!("{:?} Refusing connection {:?} from banned key", self, connection);
+        self.drop_connection(connection);
+        true
+    }
+
+    // Handles a message received from the previous hop with the name `hop_name` and the signing
+    // key `peer_key`.
     fn handle_signed_message(&mut self,
                              signed_msg: SignedMessage,
-                             hop_name: XorName)
+                             hop_content: &HopContent,
+                             hop_name: XorName,
+                             peer_key: sign::PublicKey,
+                             hops_remaining: u8,
+                             trace: &Option<Vec<TracedHop>>)
                              -> Result<(), RoutingError> {
-        try!(signed_msg.check_integrity());
-
         // Prevents
         // 1) someone sending messages repeatedly to us
         // 2) swarm messages generated by us reaching us again
-        if let Some(_) = self.signed_message_filter.insert(signed_msg.clone()) {
+        if !self.signed_message_filter.insert(peer_key, *hop_content.digest()) {
             return Err(RoutingError::FilterCheckFailed);
         }
 
@@ -395,7 +1375,7 @@ impl Core {
                 ..
             }) = signed_msg.content() {
                 // Clients with `client_restriction` are not allowed to send `GetNetworkName`.
-                if let Some(&(_, true)) = self.client_map.get(client_key) {
+                if self.client_map.get(client_key).map_or(false, |info| info.client_restriction) {
                     trace!("Illegitimate GetNetworkName request. Refusing to relay.");
                     return Err(RoutingError::ClientConnectionNotFound)
                 }
@@ -406,26 +1386,38 @@ impl Core {
                 if client_key == self.full_id.public_id().signing_public_key() {
                     if let &RoutingMessage::Request(RequestMessage { content: RequestContent::Endpoints { .. }, .. }) =
                            signed_msg.content() {
-                        try!(self.handle_signed_message_for_client(&signed_msg));
+                        try!(self.handle_signed_message_for_client(&signed_msg, peer_key));
                     }
 
                     if let &RoutingMessage::Response(ResponseMessage { content: ResponseContent::GetCloseGroup { .. }, .. }) =
                         signed_msg.content() {
-                        try!(self.handle_signed_message_for_client(&signed_msg));
+                        try!(self.handle_signed_message_for_client(&signed_msg, peer_key));
                     }
                 }
             }
-            self.handle_signed_message_for_node(&signed_msg, &hop_name)
+            self.handle_signed_message_for_node(&signed_msg,
+                                                hop_content,
+                                                &hop_name,
+                                                peer_key,
+                                                hops_remaining,
+                                                trace)
         } else if self.state == State::Client {
-            self.handle_signed_message_for_client(&signed_msg)
+            self.handle_signed_message_for_client(&signed_msg, peer_key)
         } else {
             Err(RoutingError::InvalidStateForOperation)
         }
     }
 
+    // Handles a message we received or are sending, which can be relayed over `hops_remaining`
+    // more hops. If `trace` is given, the message's route so far is returned to its source once
+    // it reaches its destination.
     fn handle_signed_message_for_node(&mut self,
                                       signed_msg: &SignedMessage,
-                                      hop_name: &XorName)
+                                      hop_content: &HopContent,
+                                      hop_name: &XorName,
+                                      peer_key: sign::PublicKey,
+                                      hops_remaining: u8,
+                                      trace: &Option<Vec<TracedHop>>)
                                       -> Result<(), RoutingError> {
         // Node Harvesting
         if self.connection_filter.insert(signed_msg.public_id().name().clone()).is_none() &&
@@ -437,21 +1429,26 @@ impl Core {
             try!(self.signed_msg_security_check(&signed_msg));
 
             if signed_msg.content().dst().is_group() {
-                try!(self.send(signed_msg.clone()));  // Swarm
+                // Swarm, and handle the message even if some members' queues are full. The dropped
+                // copies are logged and counted in `send_bytes`.
+                match self.relay(signed_msg, hop_content, hops_remaining, trace) {
+                    Ok(()) | Err(RoutingError::Interface(InterfaceError::SendQueueFull)) => (),
+                    Err(error) => return Err(error),
+                }
             } else if self.full_id.public_id().name() != signed_msg.content().dst().get_name() {
                 // TODO See if this puts caching into disadvantage
                 // Incoming msg is in our range and not for a group and also not for us, thus
                 // sending on and bailing out
-                return self.send(signed_msg.clone());  // Swarm
+                return self.relay(signed_msg, hop_content, hops_remaining, trace);  // Swarm
             } else if let Authority::Client { ref client_key, .. } = *signed_msg.content().dst() {
-                return self.relay_to_client(signed_msg.clone(), client_key);
+                return self.relay_to_client(signed_msg, hop_content, client_key);
             }
         } else {
             // If message is coming from a client who we are the proxy node for
             // send the message on to the network
             if let Authority::Client { ref proxy_node_name, .. } = *signed_msg.content().src() {
                 if proxy_node_name == self.full_id.public_id().name() {
-                    return self.send(signed_msg.clone());
+                    return self.relay(signed_msg, hop_content, hops_remaining, trace);
                 }
             }
             if !::xor_name::closer_to_target(self.full_id.public_id().name(),
@@ -483,13 +1480,24 @@ impl Core {
 
         // Forwarding the message not meant for us (transit)
         if !self.routing_table.is_close(signed_msg.content().dst().get_name()) {
-            return self.send(signed_msg.clone());
+            return self.relay(signed_msg, hop_content, hops_remaining, trace);
         }
-        self.handle_routing_message(signed_msg.content().clone(), signed_msg.public_id().clone())
+        if let Some(ref route) = *trace {
+            // Only the member of the destination authority closest to its name returns the route.
+            let dst_name = signed_msg.content().dst().get_name();
+            let our_name = *self.full_id.public_id().name();
+            if self.closest_known_nodes(dst_name, 1).first() == Some(&our_name) {
+                if let Err(error) = self.send_route_trace(signed_msg, route.clone()) {
+                    debug!("{:?} Failed to send route trace: {:?}", self, error);
+                }
+            }
+        }
+        self.handle_routing_message(signed_msg, peer_key)
     }
 
     fn handle_signed_message_for_client(&mut self,
-                                        signed_msg: &SignedMessage)
+                                        signed_msg: &SignedMessage,
+                                        peer_key: sign::PublicKey)
                                         -> Result<(), RoutingError> {
         match *signed_msg.content().dst() {
             Authority::Client { ref client_key, .. } => {
@@ -499,7 +1507,7 @@ impl Core {
             }
             _ => return Err(RoutingError::BadAuthority),
         }
-        self.handle_routing_message(signed_msg.content().clone(), signed_msg.public_id().clone())
+        self.handle_routing_message(signed_msg, peer_key)
     }
 
     fn signed_msg_security_check(&self, signed_msg: &SignedMessage) -> Result<(), RoutingError> {
@@ -560,58 +1568,99 @@ impl Core {
         }
     }
 
-    // Needs to be commented
+    // Handles a message addressed to us, or to a group we are a member of, received from the peer
+    // with the signing key `peer_key`.
     fn handle_routing_message(&mut self,
-                              routing_msg: RoutingMessage,
-                              public_id: PublicId)
+                              signed_msg: &SignedMessage,
+                              peer_key: sign::PublicKey)
                               -> Result<(), RoutingError> {
+        // Lookup responses are checked against the keys of their signer.
+        if let RoutingMessage::Response(ResponseMessage {
+            content: ResponseContent::FindNode { ref id, ref close_nodes, .. },
+            src: Authority::ManagedNode(ref src_name),
+            ..
+        }) = *signed_msg.content() {
+            return self.handle_find_node_response(signed_msg.public_id(),
+                                                  src_name,
+                                                  id,
+                                                  close_nodes.clone());
+        }
+
+        let routing_msg = signed_msg.content().clone();
+        let mut proof = None;
         if routing_msg.src().is_group() {
-            if self.grp_msg_filter.contains(&routing_msg) {
+            let digest = *signed_msg.content_digest();
+            if self.grp_msg_filter.contains(&digest) {
                 return Err(RoutingError::FilterCheckFailed);
             }
             // Don't accumulate GetCloseGroupResponse as close_group info received is unique
             // to each node in the sender group
             match routing_msg {
                 RoutingMessage::Response(ResponseMessage { content: ResponseContent::GetCloseGroup { .. }, .. }) => (),
+                // Each member challenges us individually, and we need to reply to the sender.
+                RoutingMessage::Response(ResponseMessage {
+                    content: ResponseContent::ResourceProofChallenge { seed,
+                                                                      difficulty,
+                                                                      data_size },
+                    src: Authority::NodeManager(_),
+                    dst: Authority::Client { client_key, proxy_node_name },
+                }) => {
+                    return self.handle_resource_proof_challenge(seed,
+                                                                difficulty,
+                                                                data_size,
+                                                                *signed_msg.public_id().name(),
+                                                                client_key,
+                                                                proxy_node_name);
+                }
                 _ => {
-                    if let Some(output_msg) = self.accumulate(routing_msg.clone(), &public_id) {
-                        let _ = self.grp_msg_filter.insert(output_msg.clone());
+                    if let Some(signatures) = self.accumulate(digest, signed_msg, peer_key) {
+                        let _ = self.grp_msg_filter.insert(peer_key, digest);
+                        proof = Some(GroupProof::new(signatures));
                     } else {
                         return Ok(());
                     }
                 }
             }
         }
-        self.dispatch_request_response(routing_msg)
+        self.dispatch_request_response(routing_msg, proof)
     }
 
 
     fn dispatch_request_response(&mut self,
-                                 routing_msg: RoutingMessage)
+                                 routing_msg: RoutingMessage,
+                                 proof: Option<GroupProof>)
                                  -> Result<(), RoutingError> {
         trace!("{:?} Handling - {:?}", self, routing_msg);
         match routing_msg {
             RoutingMessage::Request(msg) => self.handle_request_message(msg),
-            RoutingMessage::Response(msg) => self.handle_response_message(msg),
+            RoutingMessage::Response(msg) => self.handle_response_message(msg, proof),
         }
     }
 
+    /// Adds the sender's signature, received from the peer with the signing key `peer_key`, to
+    /// the accumulator and returns the signatures of all senders once the quorum is reached. The
+    /// message is identified by the `digest` of its content.
     fn accumulate(&mut self,
-                  message: RoutingMessage,
-                  public_id: &PublicId)
-                  -> Option<RoutingMessage> {
+                  digest: hash::sha512::Digest,
+                  signed_msg: &SignedMessage,
+                  peer_key: sign::PublicKey)
+                  -> Option<Vec<(PublicId, sign::Signature)>> {
+        // A traced message's signature covers the flag, so it couldn't be verified in a proof.
+        if signed_msg.trace_route() {
+            debug!("{:?} Ignoring a traced group message from {:?}.",
+                   self,
+                   signed_msg.public_id());
+            return None;
+        }
         // For clients we already have set it on reception of BootstrapIdentify message
         if self.state == State::Node {
             self.message_accumulator.set_quorum_size(self.routing_table.dynamic_quorum_size());
         }
 
-        if self.message_accumulator
-               .add(message.clone(), public_id.signing_public_key().clone())
-               .is_some() {
-            Some(message)
-        } else {
-            None
-        }
+        self.message_accumulator.add(digest,
+                                     signed_msg.public_id().clone(),
+                                     signed_msg.signature().clone(),
+                                     peer_key)
     }
 
     fn handle_request_message(&mut self, request_msg: RequestMessage) -> Result<(), RoutingError> {
@@ -626,14 +1675,21 @@ impl Core {
                                                      proxy_node_name,
                                                      dst_name)
             }
-            (RequestContent::ExpectCloseNode { expect_id, },
+            (RequestContent::ExpectCloseNode { expect_id, original_name, close_nodes },
              Authority::NaeManager(_),
-             Authority::NodeManager(_)) => self.handle_expect_close_node_request(expect_id),
+             Authority::NodeManager(_)) => {
+                self.handle_expect_close_node_request(expect_id, original_name, close_nodes)
+            }
             (RequestContent::GetCloseGroup,
              Authority::Client { client_key, proxy_node_name, },
              Authority::NodeManager(dst_name)) => {
                 self.handle_get_close_group_request(client_key, proxy_node_name, dst_name)
             }
+            (RequestContent::ResourceProof { seed, data, nonce },
+             Authority::Client { client_key, proxy_node_name, },
+             Authority::ManagedNode(_)) => {
+                self.handle_resource_proof(seed, data, nonce, client_key, proxy_node_name)
+            }
             (RequestContent::Endpoints { encrypted_endpoints, nonce_bytes },
              Authority::Client { client_key, proxy_node_name, },
              Authority::ManagedNode(dst_name)) => {
@@ -668,6 +1724,14 @@ impl Core {
                                                          src_name,
                                                          dst_name)
             }
+            (RequestContent::FindNode { target, id },
+             Authority::ManagedNode(src_name),
+             Authority::ManagedNode(dst_name)) => {
+                self.handle_find_node_request(target, id, src_name, dst_name)
+            }
+            (RequestContent::GetGroupIds { id }, Authority::Client { .. }, _) => {
+                self.handle_get_group_ids_request(id, request_msg.src, request_msg.dst)
+            }
             (RequestContent::Get(..), _, _) |
             (RequestContent::Put(..), _, _) |
             (RequestContent::Post(..), _, _) |
@@ -685,15 +1749,19 @@ impl Core {
     }
 
     fn handle_response_message(&mut self,
-                               response_msg: ResponseMessage)
+                               response_msg: ResponseMessage,
+                               proof: Option<GroupProof>)
                                -> Result<(), RoutingError> {
         match (response_msg.content.clone(),
                response_msg.src.clone(),
                response_msg.dst.clone()) {
-            (ResponseContent::GetNetworkName { relocated_id, },
+            (ResponseContent::GetNetworkName { relocated_id, close_nodes },
              Authority::NaeManager(_),
              Authority::Client { client_key, proxy_node_name, }) => {
-                self.handle_get_network_name_response(relocated_id, client_key, proxy_node_name)
+                self.handle_get_network_name_response(relocated_id,
+                                                      close_nodes,
+                                                      client_key,
+                                                      proxy_node_name)
             }
             (ResponseContent::GetPublicId { public_id, },
              Authority::NodeManager(_),
@@ -710,6 +1778,10 @@ impl Core {
              Authority::Client { client_key, proxy_node_name, }) => {
                 self.handle_get_close_group_response(close_group_ids, client_key, proxy_node_name)
             }
+            (ResponseContent::RouteTrace { message_signature, id, dst, route },
+             Authority::ManagedNode(_),
+             _) => self.handle_route_trace(message_signature, id, dst, route),
+            (ResponseContent::GetGroupIds { .. }, _, Authority::Client { .. }) |
             (ResponseContent::GetSuccess(..), _, _) |
             (ResponseContent::PutSuccess(..), _, _) |
             (ResponseContent::PostSuccess(..), _, _) |
@@ -718,7 +1790,7 @@ impl Core {
             (ResponseContent::PutFailure{..}, _, _) |
             (ResponseContent::PostFailure{..}, _, _) |
             (ResponseContent::DeleteFailure{..}, _, _) => {
-                let event = Event::Response(response_msg);
+                let event = Event::Response(response_msg, proof);
                 let _ = self.event_sender.send(event);
                 Ok(())
             }
@@ -731,6 +1803,17 @@ impl Core {
 
     fn handle_bootstrap_finished(&mut self) {
         debug!("Finished bootstrapping.");
+        // If all proxy nodes we found are blacklisted, e. g. because the network only has one
+        // contact, try them again instead of starting a new network.
+        if self.state == State::Disconnected && !self.bootstrap_blacklist.is_empty() {
+            if self.bootstrap_retry_time.is_none() {
+                debug!("No proxy node found apart from blacklisted ones. Clearing the blacklist.");
+                self.bootstrap_blacklist.clear();
+                self.bootstrap_retry_time = Some(SteadyTime::now() +
+                                                 Duration::seconds(BOOTSTRAP_RETRY_DELAY_SECS));
+            }
+            return;
+        }
         // If we have no connections, we should start listening to allow incoming connections
         if self.state == State::Disconnected {
             debug!("Bootstrap finished with no connections. Start Listening to allow incoming \
@@ -790,6 +1873,14 @@ impl Core {
                          connection_token: u32) {
         match result {
             Ok((endpoint, connection)) => {
+                if self.drop_if_banned_ip(connection) {
+                    return;
+                }
+                if connection_token != BOOTSTRAP_TOKEN && connection_token != self.connect_token {
+                    debug!("Dropping connection {:?} of an abandoned join attempt.", connection);
+                    self.drop_connection(connection);
+                    return;
+                }
                 self.acceptors.add(endpoint.clone());
                 debug!("New connection via OnConnect {:?} with token {}",
                        connection,
@@ -814,6 +1905,9 @@ impl Core {
 
     fn handle_on_accept(&mut self, endpoint: crust::Endpoint, connection: crust::Connection) {
         debug!("New connection via OnAccept {:?} {:?}", connection, self);
+        if self.drop_if_banned_ip(connection) {
+            return;
+        }
         if self.state == State::Disconnected {
             // I am the first node in the network, and I got an incoming connection so I'll
             // promote myself as a node.
@@ -832,11 +1926,27 @@ impl Core {
 
     fn handle_lost_connection(&mut self, connection: crust::Connection) {
         debug!("Lost connection on {:?}", connection);
+        self.forget_connection(&connection);
         self.dropped_routing_node_connection(&connection);
         self.dropped_client_connection(&connection);
         self.dropped_bootstrap_connection(&connection);
     }
 
+    // Drops the connection and forgets the state kept for its traffic. The state kept for the peer
+    // itself is up to the caller.
+    fn drop_connection(&mut self, connection: crust::Connection) {
+        self.crust_service.drop_node(connection);
+        self.forget_connection(&connection);
+    }
+
+    // Forgets the state kept for the traffic on a dropped or lost connection.
+    fn forget_connection(&mut self, connection: &crust::Connection) {
+        self.liveness.remove(connection);
+        self.verifier.remove(connection);
+        let _ = self.send_queues.remove(connection);
+        let _ = self.evicted_clients.remove(connection);
+    }
+
     fn bootstrap_identify(&mut self, connection: crust::Connection) -> Result<(), RoutingError> {
         let direct_message = DirectMessage::BootstrapIdentify {
             public_id: self.full_id.public_id().clone(),
@@ -846,13 +1956,32 @@ impl Core {
         let message = Message::DirectMessage(direct_message);
         let raw_bytes = try!(serialisation::serialise(&message));
 
-        Ok(self.crust_service.send(connection, raw_bytes))
+        self.send_bytes(connection, Priority::Control, raw_bytes)
     }
 
-    fn bootstrap_deny(&mut self, connection: crust::Connection) -> Result<(), RoutingError> {
-        let message = Message::DirectMessage(DirectMessage::BootstrapDeny);
+    fn bootstrap_queued(&mut self,
+                        connection: crust::Connection,
+                        position: usize)
+                        -> Result<(), RoutingError> {
+        let estimated_wait = self.join_queue.estimated_wait(position, MAX_JOINING_NODES);
+        let direct_message = DirectMessage::BootstrapQueued {
+            position: position,
+            estimated_wait_secs: estimated_wait.num_seconds() as u64,
+        };
+
+        let message = Message::DirectMessage(direct_message);
         let raw_bytes = try!(serialisation::serialise(&message));
-        Ok(self.crust_service.send(connection, raw_bytes))
+
+        self.send_bytes(connection, Priority::Control, raw_bytes)
+    }
+
+    fn bootstrap_deny(&mut self,
+                      connection: crust::Connection,
+                      reason: BootstrapDenyReason)
+                      -> Result<(), RoutingError> {
+        let message = Message::DirectMessage(DirectMessage::BootstrapDeny { reason: reason });
+        let raw_bytes = try!(serialisation::serialise(&message));
+        self.send_bytes(connection, Priority::Control, raw_bytes)
     }
 
     fn client_identify(&mut self, connection: crust::Connection) -> Result<(), RoutingError> {
@@ -869,7 +1998,7 @@ impl Core {
         let message = Message::DirectMessage(direct_message);
         let raw_bytes = try!(serialisation::serialise(&message));
 
-        Ok(self.crust_service.send(connection, raw_bytes))
+        self.send_bytes(connection, Priority::Control, raw_bytes)
     }
 
     fn node_identify(&mut self, connection: crust::Connection) -> Result<(), RoutingError> {
@@ -886,20 +2015,7 @@ impl Core {
         let message = Message::DirectMessage(direct_message);
         let raw_bytes = try!(serialisation::serialise(&message));
 
-        Ok(self.crust_service.send(connection, raw_bytes))
-    }
-
-    fn verify_signed_public_id(serialised_public_id: &[u8],
-                               signature: &sign::Signature)
-                               -> Result<PublicId, RoutingError> {
-        let public_id: PublicId = try!(serialisation::deserialise(serialised_public_id));
-        if sign::verify_detached(signature,
-                                 serialised_public_id,
-                                 public_id.signing_public_key()) {
-            Ok(public_id)
-        } else {
-            Err(RoutingError::FailedSignature)
-        }
+        self.send_bytes(connection, Priority::Control, raw_bytes)
     }
 
     fn handle_direct_message(&mut self,
@@ -915,23 +2031,28 @@ impl Core {
                 if *public_id.name() ==
                    XorName::new(hash::sha512::hash(&public_id.signing_public_key().0).0) {
                     warn!("Incoming Connection not validated as a proper node - dropping");
-                    self.crust_service.drop_node(connection);
+                    self.drop_connection(connection);
 
                 // Probably look for other bootstrap connections
                     return Ok(());
                 }
 
+                if self.drop_if_banned_key(connection, public_id.signing_public_key()) {
+                    return Ok(());
+                }
+
                 if let Some(previous_name) = self.proxy_map.insert(connection, public_id.clone()) {
                     warn!("Adding bootstrap node to proxy map caused a prior id to eject. \
                            Previous name: {:?}",
                           previous_name);
                     warn!("Dropping this connection {:?}", connection);
-                    self.crust_service.drop_node(connection);
+                    self.drop_connection(connection);
                     let _ = self.proxy_map.remove(&connection);
 
                     // Probably look for other bootstrap connections
                     return Ok(());
                 }
+                self.verifier.set_peer_key(connection, *public_id.signing_public_key());
 
                 self.state = State::Client;
                 self.message_accumulator.set_quorum_size(current_quorum_size);
@@ -943,187 +2064,361 @@ impl Core {
                 };
                 Ok(())
             }
-            DirectMessage::BootstrapDeny => {
-                if self.client_restriction {
-                    warn!("Connection failed: Proxy node needs a larger routing table to accept \
-                           clients.");
-                } else {
-                    warn!("Connection failed: Proxy node doesn't accept any more joining nodes.");
+            DirectMessage::BootstrapDeny { reason } => {
+                match reason {
+                    BootstrapDenyReason::RoutingTableTooSmall => {
+                        warn!("Connection failed: Proxy node needs a larger routing table to \
+                               accept clients.");
+                    }
+                    BootstrapDenyReason::JoinQueueFull => {
+                        warn!("Connection failed: Proxy node doesn't accept any more joining \
+                               nodes.");
+                    }
+                    BootstrapDenyReason::TooManyClients => {
+                        warn!("Connection failed: Proxy node doesn't accept any more clients.");
+                    }
+                    BootstrapDenyReason::Evicted => {
+                        warn!("Disconnected: Proxy node evicted us for being idle.");
+                    }
                 }
                 self.retry_bootstrap_with_blacklist(connection);
                 Ok(())
             }
-            DirectMessage::ClientIdentify { ref serialised_public_id, ref signature, client_restriction } => {
-
-                let public_id = match Core::verify_signed_public_id(serialised_public_id,
-                                                                    signature) {
-                    Ok(public_id) => public_id,
-                    Err(_) => {
-                        warn!("Signature check failed in ClientIdentify - Dropping connection {:?}",
-                              connection);
-                        self.crust_service.drop_node(connection);
+            DirectMessage::Heartbeat => {
+                self.send_direct_message(connection, DirectMessage::HeartbeatResponse)
+            }
+            DirectMessage::HopAck { digest } => {
+                if let Some(node_info) = self.routing_node_on(&connection) {
+                    self.pending_acks.acked(&digest, node_info.public_id.name());
+                }
+                Ok(())
+            }
+            DirectMessage::HeartbeatResponse => {
+                self.liveness.heard_response(&connection, SteadyTime::now());
+                Ok(())
+            }
+            DirectMessage::RateLimitExceeded { retry_after_ms } => {
+                if !self.proxy_map.contains_key(&connection) {
+                    return Err(RoutingError::UnknownConnection);
+                }
+                warn!("{:?} Proxy node rejected a message: rate limit exceeded.", self);
+                let event = Event::RateLimitExceeded {
+                    retry_after: retry_after_ms.map(::std::time::Duration::from_millis),
+                };
+                let _ = self.event_sender.send(event);
+                Ok(())
+            }
+            DirectMessage::BootstrapQueued { position, estimated_wait_secs } => {
+                trace!("{:?} Waiting to join: position {} in queue, estimated wait {}s.",
+                       self,
+                       position,
+                       estimated_wait_secs);
+                let _ = self.event_sender.send(Event::JoinQueued {
+                    position: position,
+                    estimated_wait: ::std::time::Duration::from_secs(estimated_wait_secs),
+                });
+                Ok(())
+            }
+            DirectMessage::ClientIdentify { .. } |
+            DirectMessage::NodeIdentify { .. } => {
+                // Identify messages are handled by `handle_identify` once their signature has
+                // been checked.
+                Err(RoutingError::UnknownMessageType)
+            }
+        }
+    }
+
+    // Handles a `ClientIdentify` or `NodeIdentify` message once the verifier has checked the
+    // signature of the `public_id` it carries.
+    fn handle_identify(&mut self,
+                       direct_message: DirectMessage,
+                       public_id: Result<PublicId, RoutingError>,
+                       connection: crust::Connection)
+                       -> Result<(), RoutingError> {
+        let public_id = match public_id {
+            Ok(public_id) => public_id,
+            Err(_) => {
+                warn!("Signature check failed in identify message - Dropping connection {:?}",
+                      connection);
+                self.drop_connection(connection);
+                return Ok(());
+            }
+        };
+        match direct_message {
+            DirectMessage::ClientIdentify { client_restriction, .. } => {
+                self.handle_client_identify(public_id, client_restriction, connection)
+            }
+            DirectMessage::NodeIdentify { .. } => self.handle_node_identify(public_id, connection),
+            _ => Err(RoutingError::UnknownMessageType),
+        }
+    }
 
-                        return Ok(());
-                    }
-                };
+    fn handle_client_identify(&mut self,
+                              public_id: PublicId,
+                              client_restriction: bool,
+                              connection: crust::Connection)
+                              -> Result<(), RoutingError> {
+        if *public_id.name() !=
+           XorName::new(hash::sha512::hash(&public_id.signing_public_key().0).0) {
+            warn!("Incoming Connection not validated as a proper client - dropping");
+            self.drop_connection(connection);
+            return Ok(());
+        }
 
-                if *public_id.name() !=
-                   XorName::new(hash::sha512::hash(&public_id.signing_public_key().0).0) {
-                    warn!("Incoming Connection not validated as a proper client - dropping");
-                    self.crust_service.drop_node(connection);
-                    return Ok(());
-                }
+        if self.drop_if_banned_key(connection, public_id.signing_public_key()) {
+            return Ok(());
+        }
 
-                let group_size = kademlia_routing_table::group_size();
-                if client_restriction {
-                    if self.routing_table.len() < group_size {
-                        trace!("Client rejected: Routing table has {} entries. {} required.",
-                               self.routing_table.len(), group_size);
-                        return self.bootstrap_deny(connection);
-                    }
-                } else {
-                    let joining_nodes_num = self.joining_nodes_num();
-                    // Restrict the number of simultaneously joining nodes. If the network is still
-                    // small, we need to accept `group_size` nodes, so that they can fill their
-                    // routing tables and drop the proxy connection.
-                    if !(self.routing_table.len() < group_size && joining_nodes_num < group_size)
-                            && joining_nodes_num >= MAX_JOINING_NODES  {
-                        trace!("No additional joining nodes allowed.");
-                        return self.bootstrap_deny(connection);
-                    }
+        let group_size = kademlia_routing_table::group_size();
+        if client_restriction {
+            if self.routing_table.len() < group_size {
+                trace!("Client rejected: Routing table has {} entries. {} required.",
+                       self.routing_table.len(), group_size);
+                return self.bootstrap_deny(connection,
+                                           BootstrapDenyReason::RoutingTableTooSmall);
+            }
+            if !self.client_map.contains_key(public_id.signing_public_key()) &&
+               !self.make_room_for_client() {
+                trace!("Client rejected: Already serving {} clients.",
+                       self.config.max_clients);
+                return self.bootstrap_deny(connection, BootstrapDenyReason::TooManyClients);
+            }
+        } else if !self.join_queue.is_empty() || !self.accepts_joining_node() {
+            // Nodes that are already waiting go first.
+            return match self.join_queue
+                             .push(connection, *public_id.signing_public_key()) {
+                Some(position) => {
+                    trace!("Joining node queued at position {}.", position);
+                    self.bootstrap_queued(connection, position)
                 }
-                if let Some((prev_conn, _)) = self.client_map
-                                                  .insert(public_id.signing_public_key().clone(),
-                                                          (connection, client_restriction)) {
-                    debug!("Found previous connection against client key - Dropping {:?}",
-                           prev_conn);
-                    self.crust_service.drop_node(prev_conn);
+                None => {
+                    trace!("No additional joining nodes allowed.");
+                    self.bootstrap_deny(connection, BootstrapDenyReason::JoinQueueFull)
                 }
+            };
+        }
 
-                let _ = self.bootstrap_identify(connection);
-                Ok(())
-            }
-            DirectMessage::NodeIdentify { ref serialised_public_id, ref signature } => {
-                let public_id = match Core::verify_signed_public_id(serialised_public_id,
-                                                                    signature) {
-                    Ok(public_id) => public_id,
-                    Err(_) => {
-                        warn!("Signature check failed in NodeIdentify - Dropping connection {:?}",
-                              connection);
-                        self.crust_service.drop_node(connection);
+        self.accept_client(connection,
+                           *public_id.signing_public_key(),
+                           client_restriction);
+        Ok(())
+    }
 
-                        return Ok(());
-                    }
-                };
+    fn handle_node_identify(&mut self,
+                            public_id: PublicId,
+                            connection: crust::Connection)
+                            -> Result<(), RoutingError> {
+        if self.drop_if_banned_key(connection, public_id.signing_public_key()) {
+            return Ok(());
+        }
 
-                if let Some(their_public_id) = self.node_id_cache.get(public_id.name()).cloned() {
-                    if their_public_id != public_id {
-                        warn!("Given Public ID and Public ID in cache don't match - Given {:?} \
-                               :: In cache {:?} Dropping connection {:?}",
-                              public_id,
-                              their_public_id,
-                              connection);
+        if let Some(their_public_id) = self.node_id_cache.get(public_id.name()).cloned() {
+            if their_public_id != public_id {
+                warn!("Given Public ID and Public ID in cache don't match - Given {:?} \
+                       :: In cache {:?} Dropping connection {:?}",
+                      public_id,
+                      their_public_id,
+                      connection);
 
-                        self.crust_service.drop_node(connection);
-                        return Ok(());
-                    }
+                self.drop_connection(connection);
+                return Ok(());
+            }
 
-                    let node_info = NodeInfo::new(public_id.clone(), vec![connection]);
-                    if let Some(_) = self.routing_table.get(public_id.name()) {
-                        if !self.routing_table.add_connection(public_id.name(), connection) {
-                            // We already sent an identify down this connection
-                            return Ok(());
+            let node_info = NodeInfo::new(public_id.clone(), vec![connection]);
+            if let Some(_) = self.routing_table.get(public_id.name()) {
+                if !self.routing_table.add_connection(public_id.name(), connection) {
+                    // We already sent an identify down this connection
+                    return Ok(());
+                }
+            } else {
+                if self.routing_table.is_close(public_id.name()) {
+                    // If the new node is going to displace a node from the close group then
+                    // inform the vaults about the node being moved out of close group
+                    let lost_close_node = if self.routing_table.len() >=
+                                             kademlia_routing_table::group_size() {
+                        if let Some(last_close_node) = self.routing_table
+                                                           .our_close_group()
+                                                           .last() {
+                            Some(last_close_node.public_id.name().clone())
+                        } else {
+                            None
                         }
                     } else {
-                        if self.routing_table.is_close(public_id.name()) {
-                            // If the new node is going to displace a node from the close group then
-                            // inform the vaults about the node being moved out of close group
-                            let lost_close_node = if self.routing_table.len() >=
-                                                     kademlia_routing_table::group_size() {
-                                if let Some(last_close_node) = self.routing_table
-                                                                   .our_close_group()
-                                                                   .last() {
-                                    Some(last_close_node.public_id.name().clone())
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            };
-
-                            // send churn
-                            let event = Event::Churn {
-                                id: MessageId::from_added_node(public_id.name().clone()),
-                                lost_close_node: lost_close_node,
-                            };
-
-                            if let Err(err) = self.event_sender.send(event) {
-                                error!("Error sending event to routing user - {:?}", err);
-                            }
-                        }
+                        None
+                    };
 
-                        let (is_added, node_removed) = self.routing_table.add_node(node_info);
+                    // send churn
+                    let event = Event::Churn {
+                        id: MessageId::from_added_node(public_id.name().clone()),
+                        lost_close_node: lost_close_node,
+                    };
 
-                        if !is_added {
-                            self.crust_service.drop_node(connection);
-                            let _ = self.node_id_cache.remove(public_id.name());
+                    if let Err(err) = self.event_sender.send(event) {
+                        error!("Error sending event to routing user - {:?}", err);
+                    }
+                }
 
-                            return Ok(());
-                        }
+                let (is_added, node_removed) = self.routing_table.add_node(node_info);
 
-                        if self.routing_table.len() >= kademlia_routing_table::group_size()
-                                && !self.proxy_map.is_empty() {
-                            trace!("Routing table reached group size. Dropping proxy.");
-                            self.proxy_map.keys()
-                                .foreach(|&connection| self.crust_service.drop_node(connection));
-                            self.proxy_map.clear();
-                        }
+                if !is_added {
+                    self.drop_connection(connection);
+                    let _ = self.node_id_cache.remove(public_id.name());
 
-                        self.state = State::Node;
+                    return Ok(());
+                }
 
-                        if let Some(node_to_drop) = node_removed {
-                            debug!("Node ejected by routing table on an add. Dropping node {:?}",
-                                   node_to_drop);
+                let bucket_index = utils::bucket_index(self.full_id.public_id().name(),
+                                                       public_id.name());
+                let _ = self.bucket_refresh_times.insert(bucket_index, SteadyTime::now());
 
-                            for it in node_to_drop.connections.into_iter() {
-                                self.crust_service.drop_node(it);
-                            }
-                        }
-                    }
+                if self.routing_table.len() >= kademlia_routing_table::group_size()
+                        && !self.proxy_map.is_empty() {
+                    trace!("Routing table reached group size. Dropping proxy.");
+                    let proxies = self.proxy_map.keys().cloned().collect_vec();
+                    proxies.into_iter().foreach(|connection| self.drop_connection(connection));
+                    self.proxy_map.clear();
+                }
 
-                    let _ = self.node_identify(connection);
-                    return Ok(());
-                } else {
-                    debug!("PublicId not found in node_id_cache - Dropping Connection {:?}",
-                           connection);
-                    self.crust_service.drop_node(connection);
-                    return Ok(());
+                self.state = State::Node;
+                self.join_stage = None;
+
+                if let Some(node_to_drop) = node_removed {
+                    debug!("Node ejected by routing table on an add. Dropping node {:?}",
+                           node_to_drop);
+
+                    for it in node_to_drop.connections.into_iter() {
+                        self.drop_connection(it);
+                    }
                 }
             }
+
+            self.verifier.set_peer_key(connection, *public_id.signing_public_key());
+            let _ = self.node_identify(connection);
+            return Ok(());
+        } else {
+            debug!("PublicId not found in node_id_cache - Dropping Connection {:?}",
+                   connection);
+            self.drop_connection(connection);
+            return Ok(());
         }
     }
 
     /// Returns the number of clients for which we act as a proxy and which intend to become a
     /// node.
     fn joining_nodes_num(&self) -> usize {
-        self.client_map.values().filter(|&&(_, client_restriction)| !client_restriction).count()
+        self.client_map.values().filter(|info| !info.client_restriction).count()
+    }
+
+    // Restrict the number of simultaneously joining nodes. If the network is still small, we need
+    // to accept `group_size` nodes, so that they can fill their routing tables and drop the proxy
+    // connection.
+    fn accepts_joining_node(&self) -> bool {
+        let group_size = kademlia_routing_table::group_size();
+        let joining_nodes_num = self.joining_nodes_num();
+        (self.routing_table.len() < group_size && joining_nodes_num < group_size) ||
+        joining_nodes_num < MAX_JOINING_NODES
+    }
+
+    fn accept_client(&mut self,
+                     connection: crust::Connection,
+                     public_key: sign::PublicKey,
+                     client_restriction: bool) {
+        let burst = Duration::seconds(self.config.client_rate_burst.as_secs() as i64);
+        let client_info = ClientInfo {
+            connection: connection,
+            client_restriction: client_restriction,
+            rate_limiter: RateLimiter::new(self.config.client_message_rate,
+                                           self.config.client_byte_rate,
+                                           burst,
+                                           SteadyTime::now()),
+            rate_limit_violations: 0,
+            last_activity: SteadyTime::now(),
+        };
+        if let Some(prev_info) = self.client_map.insert(public_key, client_info) {
+            debug!("Found previous connection against client key - Dropping {:?}",
+                   prev_info.connection);
+            self.drop_connection(prev_info.connection);
+        }
+        self.verifier.set_peer_key(connection, public_key);
+        if !client_restriction {
+            self.join_queue.start_join(connection);
+        }
+
+        let _ = self.bootstrap_identify(connection);
+    }
+
+    // Returns whether another client can be accepted. If we are already serving the maximum number
+    // of clients, the one that has been idle the longest is evicted, if it has been idle for long
+    // enough.
+    fn make_room_for_client(&mut self) -> bool {
+        let client_count = self.client_map.len() - self.joining_nodes_num();
+        if client_count < self.config.max_clients {
+            return true;
+        }
+        let min_idle_secs = self.config.client_eviction_idle_time.as_secs();
+        let min_idle_time = Duration::seconds(min_idle_secs as i64);
+        let idlest = self.client_map
+                         .values()
+                         .filter(|info| info.client_restriction)
+                         .min_by_key(|info| info.last_activity)
+                         .map(|info| (info.connection, info.last_activity));
+        let now = SteadyTime::now();
+        match idlest {
+            Some((connection, last_activity)) if now - last_activity >= min_idle_time => {
+                debug!("{:?} Evicting idle client on {:?} to make room for a new one.",
+                       self,
+                       connection);
+                self.dropped_client_connection(&connection);
+                let _ = self.bootstrap_deny(connection, BootstrapDenyReason::Evicted);
+                let _ = self.evicted_clients.insert(connection);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Accept waiting joining nodes while there are free slots, and inform the remaining ones
+    // about their new positions.
+    fn admit_queued_joining_nodes(&mut self) {
+        while self.accepts_joining_node() {
+            match self.join_queue.pop() {
+                Some((connection, public_key)) => {
+                    trace!("Admitting queued joining node on {:?}.", connection);
+                    self.accept_client(connection, public_key, false);
+                }
+                None => return,
+            }
+        }
+        for (index, connection) in self.join_queue.keys().into_iter().enumerate() {
+            let _ = self.bootstrap_queued(connection, index + 1);
+        }
     }
 
     fn retry_bootstrap_with_blacklist(&mut self, connection: crust::Connection) {
-        let _endpoint = connection.peer_endpoint();
-        self.crust_service.drop_node(connection);
+        let endpoint = connection.peer_endpoint();
+        if !self.bootstrap_blacklist.contains(&endpoint) {
+            self.bootstrap_blacklist.push(endpoint);
+        }
+        self.drop_connection(connection);
         self.crust_service.stop_bootstrap();
+        self.bootstrap_retry_time = Some(SteadyTime::now() +
+                                         Duration::seconds(BOOTSTRAP_RETRY_DELAY_SECS));
+    }
+
+    fn restart_bootstrap(&mut self) {
+        self.bootstrap_retry_time = None;
         self.state = State::Disconnected;
-        for &connection in self.proxy_map.keys() {
-            self.crust_service.drop_node(connection);
+        for connection in self.proxy_map.keys().cloned().collect_vec() {
+            self.drop_connection(connection);
         }
         self.proxy_map.clear();
-        thread::sleep(::std::time::Duration::from_secs(5));
-        self.crust_service.bootstrap(0u32, Some(CRUST_DEFAULT_BEACON_PORT));
-        //TODO(andreas): Enable blacklisting once a solution for ci_test is found.
-        //               Currently, ci_test's nodes all connect via the same beacon.
-        //self.crust_service
-        //    .bootstrap_with_blacklist(0u32, Some(CRUST_DEFAULT_BEACON_PORT), &[endpoint]);
+        if self.bootstrap_blacklist.is_empty() {
+            self.crust_service.bootstrap(BOOTSTRAP_TOKEN, Some(CRUST_DEFAULT_BEACON_PORT));
+        } else {
+            self.crust_service.bootstrap_with_blacklist(BOOTSTRAP_TOKEN,
+                                                        Some(CRUST_DEFAULT_BEACON_PORT),
+                                                        &self.bootstrap_blacklist);
+        }
     }
 
     // Constructed by A; From A -> X
@@ -1142,7 +2437,9 @@ impl Core {
 
         let signed_message = try!(SignedMessage::new(routing_msg, &self.full_id));
 
-        self.send(signed_message)
+        try!(self.send(signed_message));
+        self.set_join_stage(JoinStage::Relocating);
+        Ok(())
     }
 
     // Received by X; From A -> X
@@ -1160,10 +2457,17 @@ impl Core {
             return Err(RoutingError::InvalidDestination);
         }
 
+        // Validate the supplied `PublicId` is the client's own, unrelocated one
+        if *their_public_id.signing_public_key() != client_key ||
+           *their_public_id.name() != close_group_to_client {
+            return Err(RoutingError::RejectedPublicId);
+        }
+
         let mut close_group = self.close_group_names();
         close_group.push(self.full_id.public_id().name().clone());
-        let relocated_name = try!(utils::calculate_relocated_name(close_group,
-                                                                  &their_public_id.name()));
+        let original_name = *their_public_id.name();
+        let close_nodes = try!(utils::relocation_close_nodes(close_group, &original_name));
+        let relocated_name = utils::hash_relocated_name(&original_name, &close_nodes);
 
         their_public_id.set_name(relocated_name.clone());
 
@@ -1171,6 +2475,7 @@ impl Core {
         {
             let response_content = ResponseContent::GetNetworkName {
                 relocated_id: their_public_id.clone(),
+                close_nodes: close_nodes.clone(),
             };
 
             let response_msg = ResponseMessage {
@@ -1192,6 +2497,8 @@ impl Core {
         {
             let request_content = RequestContent::ExpectCloseNode {
                 expect_id: their_public_id.clone(),
+                original_name: original_name,
+                close_nodes: close_nodes,
             };
 
             let request_msg = RequestMessage {
@@ -1210,8 +2517,21 @@ impl Core {
 
     // Received by Y; From X -> Y
     fn handle_expect_close_node_request(&mut self,
-                                        expect_id: PublicId)
+                                        expect_id: PublicId,
+                                        original_name: XorName,
+                                        close_nodes: Vec<XorName>)
                                         -> Result<(), RoutingError> {
+        // The original name must be the one the joining node's keys entitle it to, and the
+        // relocated name must have been computed from it.
+        if XorName(hash::sha512::hash(&expect_id.signing_public_key().0).0) != original_name {
+            warn!("Original name {:?} does not belong to {:?}. Rejecting relocation.",
+                  original_name,
+                  expect_id);
+            return Err(RoutingError::InvalidRelocation);
+        }
+        try!(utils::verify_relocated_name(&original_name, &close_nodes, expect_id.name()));
+        try!(self.verify_relocation_close_nodes(&original_name, &close_nodes));
+
         if let Some(prev_id) = self.node_id_cache.insert(*expect_id.name(), expect_id) {
             warn!("Previous id {:?} with same name found during \
                    handle_expect_close_node_request. Ignoring that",
@@ -1222,13 +2542,56 @@ impl Core {
         Ok(())
     }
 
+    // Checks that none of the nodes we know about, including ourselves, is closer to
+    // `original_name` than the furthest of the `close_nodes` the relocated name was computed from,
+    // without being one of them.
+    fn verify_relocation_close_nodes(&self,
+                                     original_name: &XorName,
+                                     close_nodes: &[XorName])
+                                     -> Result<(), RoutingError> {
+        let furthest = match close_nodes.last() {
+            Some(name) => name,
+            None => return Err(RoutingError::InvalidRelocation),
+        };
+        let our_name = self.full_id.public_id().name();
+        let known_names = self.routing_table
+                              .closest_nodes_to(original_name, close_nodes.len(), false)
+                              .into_iter()
+                              .map(|node_info| *node_info.public_id.name())
+                              .chain(Some(*our_name));
+        for name in known_names {
+            if !close_nodes.contains(&name) &&
+               ::xor_name::closer_to_target(&name, furthest, original_name) {
+                warn!("{:?} is closer to {:?} than the relocation close nodes {:?}. Rejecting \
+                       relocation.",
+                      name,
+                      original_name,
+                      close_nodes);
+                return Err(RoutingError::InvalidRelocation);
+            }
+        }
+        Ok(())
+    }
+
     // Received by A; From X -> A
     fn handle_get_network_name_response(&mut self,
                                         relocated_id: PublicId,
+                                        close_nodes: Vec<XorName>,
                                         client_key: sign::PublicKey,
                                         proxy_name: XorName)
                                         -> Result<(), RoutingError> {
+        // We may already have a new name, or have given up on this relocation.
+        if !self.is_join_stage(JoinStage::Relocating) {
+            return Err(RoutingError::InvalidStateForOperation);
+        }
+        if *relocated_id.signing_public_key() != client_key {
+            return Err(RoutingError::RejectedPublicId);
+        }
+        let original_name = *self.full_id.public_id().name();
+        try!(utils::verify_relocated_name(&original_name, &close_nodes, relocated_id.name()));
+
         self.set_self_node_name(*relocated_id.name());
+        self.set_join_stage(JoinStage::GettingCloseGroup);
 
         let request_content = RequestContent::GetCloseGroup;
 
@@ -1255,6 +2618,161 @@ impl Core {
                                       proxy_name: XorName,
                                       dst_name: XorName)
                                       -> Result<(), RoutingError> {
+        if self.config.resource_proof_difficulty == 0 && self.config.resource_proof_size == 0 {
+            return self.send_close_group(client_key, proxy_name, dst_name);
+        }
+
+        let seed = resource_proof::create_seed();
+        let _ = self.resource_proof_challenges.insert(client_key, (seed.clone(), dst_name));
+
+        let response_content = ResponseContent::ResourceProofChallenge {
+            seed: seed,
+            difficulty: self.config.resource_proof_difficulty,
+            data_size: self.config.resource_proof_size,
+        };
+
+        let response_msg = ResponseMessage {
+            src: Authority::NodeManager(dst_name),
+            dst: Authority::Client {
+                client_key: client_key,
+                proxy_node_name: proxy_name,
+            },
+            content: response_content,
+        };
+
+        let routing_message = RoutingMessage::Response(response_msg);
+
+        let signed_message = try!(SignedMessage::new(routing_message, &self.full_id));
+
+        self.send(signed_message)
+    }
+
+    // Received by A; From each member of Y -> A
+    fn handle_resource_proof_challenge(&mut self,
+                                       seed: Vec<u8>,
+                                       difficulty: u8,
+                                       data_size: usize,
+                                       member_name: XorName,
+                                       client_key: sign::PublicKey,
+                                       proxy_name: XorName)
+                                       -> Result<(), RoutingError> {
+        if client_key != *self.full_id.public_id().signing_public_key() {
+            return Err(RoutingError::BadAuthority);
+        }
+        if !self.is_join_stage(JoinStage::GettingCloseGroup) {
+            return Err(RoutingError::InvalidStateForOperation);
+        }
+        // The challenge hasn't been accumulated, so it could come from any single node.
+        if difficulty > self.config.max_resource_proof_difficulty ||
+           data_size > self.config.max_resource_proof_size {
+            warn!("{:?} Ignoring resource proof challenge from {:?} with difficulty {} and size \
+                   {}.",
+                  self,
+                  member_name,
+                  difficulty,
+                  data_size);
+            return Err(RoutingError::InvalidResourceProof);
+        }
+        if self.solving_resource_proofs.contains(&member_name) ||
+           self.solving_resource_proofs.len() >= kademlia_routing_table::group_size() {
+            return Err(RoutingError::InvalidResourceProof);
+        }
+        let _ = self.solving_resource_proofs.insert(member_name);
+
+        // Solving takes a while, so do it without blocking the routing thread.
+        let action_sender = RoutingActionSender::new(self.action_tx.clone(),
+                                                     MaidSafeEventCategory::RoutingEvent,
+                                                     self.category_tx.clone());
+        let _ = thread!("RoutingResourceProof", move || {
+            let data = resource_proof::create_data(&seed, data_size);
+            let nonce = resource_proof::solve(&seed, &data, difficulty);
+            let _ = action_sender.send(Action::ResourceProofSolved {
+                seed: seed,
+                data: data,
+                nonce: nonce,
+                member_name: member_name,
+                client_key: client_key,
+                proxy_name: proxy_name,
+            });
+        });
+        Ok(())
+    }
+
+    // Sends the solved resource proof to the member of Y that challenged us; From A -> Y member
+    fn send_resource_proof(&mut self,
+                           seed: Vec<u8>,
+                           data: Vec<u8>,
+                           nonce: u64,
+                           member_name: XorName,
+                           client_key: sign::PublicKey,
+                           proxy_name: XorName)
+                           -> Result<(), RoutingError> {
+        // We may have restarted the join in the meantime.
+        if !self.solving_resource_proofs.remove(&member_name) ||
+           client_key != *self.full_id.public_id().signing_public_key() ||
+           !self.is_join_stage(JoinStage::GettingCloseGroup) {
+            return Ok(());
+        }
+
+        let request_content = RequestContent::ResourceProof {
+            seed: seed,
+            data: data,
+            nonce: nonce,
+        };
+
+        // From A -> Y member
+        let request_msg = RequestMessage {
+            src: Authority::Client {
+                client_key: client_key,
+                proxy_node_name: proxy_name,
+            },
+            dst: Authority::ManagedNode(member_name),
+            content: request_content,
+        };
+
+        let routing_msg = RoutingMessage::Request(request_msg);
+
+        let signed_msg = try!(SignedMessage::new(routing_msg, &self.full_id));
+
+        self.send(signed_msg)
+    }
+
+    // Received by a member of Y; From A -> Y member
+    fn handle_resource_proof(&mut self,
+                             seed: Vec<u8>,
+                             data: Vec<u8>,
+                             nonce: u64,
+                             client_key: sign::PublicKey,
+                             proxy_name: XorName)
+                             -> Result<(), RoutingError> {
+        let dst_name = match self.resource_proof_challenges.remove(&client_key) {
+            Some((expected_seed, dst_name)) => {
+                if expected_seed != seed {
+                    return Err(RoutingError::InvalidResourceProof);
+                }
+                dst_name
+            }
+            None => return Err(RoutingError::InvalidResourceProof),
+        };
+
+        if !resource_proof::validate(&seed,
+                                     self.config.resource_proof_size,
+                                     self.config.resource_proof_difficulty,
+                                     &data,
+                                     nonce) {
+            warn!("Invalid resource proof from {:?}.", client_key);
+            return Err(RoutingError::InvalidResourceProof);
+        }
+
+        self.send_close_group(client_key, proxy_name, dst_name)
+    }
+
+    // From each member of Y -> A
+    fn send_close_group(&mut self,
+                        client_key: sign::PublicKey,
+                        proxy_name: XorName,
+                        dst_name: XorName)
+                        -> Result<(), RoutingError> {
         let mut public_ids = self.routing_table
                                  .our_close_group()
                                  .into_iter()
@@ -1288,6 +2806,9 @@ impl Core {
                                        client_key: sign::PublicKey,
                                        proxy_name: XorName)
                                        -> Result<(), RoutingError> {
+        if self.is_join_stage(JoinStage::GettingCloseGroup) {
+            self.set_join_stage(JoinStage::Connecting);
+        }
         self.start_listening();
 
         // From A -> Each in Y
@@ -1314,12 +2835,10 @@ impl Core {
         trace!("{:?} sending endpoints {:?}",
                self,
                self.acceptors.endpoints());
-        let encoded_endpoints = try!(serialisation::serialise(&self.acceptors.endpoints()));
-        let nonce = box_::gen_nonce();
-        let encrypted_endpoints = box_::seal(&encoded_endpoints,
-                                             &nonce,
-                                             their_public_id.encrypting_public_key(),
-                                             self.full_id.encrypting_private_key());
+        let (encrypted_endpoints, nonce) =
+            try!(utils::encrypt_endpoints(&self.acceptors.endpoints(),
+                                          their_public_id.encrypting_public_key(),
+                                          self.full_id.encrypting_private_key()));
 
         let request_content = RequestContent::Endpoints {
             encrypted_endpoints: encrypted_endpoints,
@@ -1552,61 +3071,204 @@ impl Core {
                nonce_bytes: [u8; box_::NONCEBYTES],
                their_public_key: &box_::PublicKey)
                -> Result<(), RoutingError> {
-        let decipher_result = box_::open(&encrypted_endpoints,
-                                         &box_::Nonce(nonce_bytes),
-                                         their_public_key,
-                                         self.full_id.encrypting_private_key());
-
-        let serialised_endpoints = try!(decipher_result.map_err(|()| {
-            RoutingError::AsymmetricDecryptionFailure
-        }));
-        let endpoints = try!(serialisation::deserialise(&serialised_endpoints));
+        // The endpoints are decrypted by the verifier, which then calls `connect_to_endpoints`.
+        self.verifier.decrypt_endpoints(encrypted_endpoints,
+                                        nonce_bytes,
+                                        *their_public_key,
+                                        self.connect_token);
+        Ok(())
+    }
 
-        self.crust_service.connect(0u32, endpoints);
+    // Connects to the decrypted endpoints of a node, unless they are banned or the connection
+    // attempt with the given `token` has been abandoned in the meantime.
+    fn connect_to_endpoints(&mut self, token: u32, endpoints: Vec<crust::Endpoint>) {
+        if token != self.connect_token {
+            debug!("{:?} Not connecting to endpoints of an abandoned join attempt.", self);
+            return;
+        }
+        let now = SteadyTime::now();
+        let endpoints = endpoints.into_iter()
+                                 .filter(|endpoint| {
+                                     let ip = utils::ip_from_socketaddr(endpoint.get_address());
+                                     !self.banned_ips.is_banned(&ip, now)
+                                 })
+                                 .collect_vec();
+        if endpoints.is_empty() {
+            return;
+        }
 
-        Ok(())
+        self.crust_service.connect(self.connect_token, endpoints);
     }
 
     // ----- Send Functions -----------------------------------------------------------------------
 
-    fn send_message(&mut self, routing_msg: RoutingMessage) -> Result<(), RoutingError> {
+    fn send_message(&mut self,
+                    routing_msg: RoutingMessage,
+                    trace_route: bool)
+                    -> Result<(), RoutingError> {
+        try!(self.check_data_size(&routing_msg));
+        // Messages from groups are not traced, so that their signatures can form a group proof.
+        let trace_route = trace_route && !routing_msg.src().is_group();
         // TODO crust should return the routing msg when it detects an interface error
-        let signed_msg = try!(SignedMessage::new(routing_msg.clone(), &self.full_id));
+        let signed_msg = try!(SignedMessage::with_trace(routing_msg.clone(),
+                                                        &self.full_id,
+                                                        trace_route));
 
         self.send(signed_msg)
     }
 
+    // Returns an error if the message carries data larger than the maximum for its type.
+    fn check_data_size(&self, routing_msg: &RoutingMessage) -> Result<(), RoutingError> {
+        if let Some(data) = routing_msg.data() {
+            let max_size = self.config.max_data_size(data);
+            if data.payload_size() > max_size {
+                return Err(RoutingError::Interface(InterfaceError::DataTooLarge {
+                    size: data.payload_size(),
+                    max_size: max_size,
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    // Checks a message of `size` bytes received on the connection of a client we are the proxy
+    // node for: if it claims to come from a client, we must be its proxy node, and it must not
+    // exceed the client's rate limit. If the limit is exceeded, the client is informed, and
+    // disconnected once too many consecutive messages have been rejected.
+    fn check_client_message(&mut self,
+                            client_key: &sign::PublicKey,
+                            src: &Authority,
+                            size: usize)
+                            -> Result<(), RoutingError> {
+        if let Authority::Client { ref proxy_node_name, .. } = *src {
+            if proxy_node_name != self.full_id.public_id().name() {
+                debug!("{:?} Client message names {:?} as its proxy node.",
+                       self,
+                       proxy_node_name);
+                return Err(RoutingError::InvalidSource);
+            }
+        }
+        let (connection, retry_after, violations) = match self.client_map.get_mut(client_key) {
+            Some(client_info) => {
+                client_info.last_activity = SteadyTime::now();
+                match client_info.rate_limiter.allow(size as u64, SteadyTime::now()) {
+                    Ok(()) => {
+                        client_info.rate_limit_violations = 0;
+                        return Ok(());
+                    }
+                    Err(retry_after) => {
+                        client_info.rate_limit_violations += 1;
+                        (client_info.connection, retry_after, client_info.rate_limit_violations)
+                    }
+                }
+            }
+            None => return Ok(()),
+        };
+
+        if violations > self.config.client_rate_limit_violations {
+            warn!("{:?} Client exceeded its rate limit {} times - Dropping connection {:?}",
+                  self,
+                  violations,
+                  connection);
+            self.crust_service.drop_node(connection);
+            self.handle_lost_connection(connection);
+        } else {
+            let direct_message = DirectMessage::RateLimitExceeded {
+                retry_after_ms: retry_after.map(|wait| wait.num_milliseconds() as u64),
+            };
+            let _ = self.send_direct_message(connection, direct_message);
+        }
+        Err(RoutingError::RateLimitExceeded)
+    }
+
     fn relay_to_client(&mut self,
-                       signed_msg: SignedMessage,
+                       signed_msg: &SignedMessage,
+                       hop_content: &HopContent,
                        client_key: &sign::PublicKey)
                        -> Result<(), RoutingError> {
-        if let Some(&(connection, _)) = self.client_map.get(client_key) {
-            let hop_msg = try!(HopMessage::new(signed_msg,
+        if let Some(connection) = self.client_map.get(client_key).map(|info| info.connection) {
+            let priority = Priority::of(signed_msg.content());
+            let hop_msg = try!(HopMessage::new(hop_content.clone(),
                                                self.full_id.public_id().name().clone(),
+                                               false,
+                                               0,
+                                               None,
                                                self.full_id.signing_private_key()));
             let message = Message::HopMessage(hop_msg);
             let raw_bytes = try!(serialisation::serialise(&message));
 
-            return Ok(self.crust_service.send(connection.clone(), raw_bytes))
+            return self.send_bytes(connection, priority, raw_bytes);
         }
 
         Err(RoutingError::ClientConnectionNotFound)
     }
 
+    // Sends a message originating from us, which can be relayed over the configured number of hops.
     fn send(&mut self, signed_msg: SignedMessage) -> Result<(), RoutingError> {
-        let hop_msg = try!(HopMessage::new(signed_msg.clone(),
+        let hop_limit = self.config.hop_limit;
+        let trace = if signed_msg.trace_route() {
+            let _ = self.traced_messages.insert(*signed_msg.signature(), ());
+            Some(vec![])
+        } else {
+            None
+        };
+        let hop_content = try!(HopContent::new(&signed_msg));
+        self.relay(&signed_msg, &hop_content, hop_limit, &trace)
+    }
+
+    // Sends the message to the next hops, if it can be relayed over `hops_remaining` more hops.
+    // If the route is traced, we add ourselves to it. The serialised message in `hop_content` is
+    // passed on as it is.
+    fn relay(&mut self,
+             signed_msg: &SignedMessage,
+             hop_content: &HopContent,
+             hops_remaining: u8,
+             trace: &Option<Vec<TracedHop>>)
+             -> Result<(), RoutingError> {
+        if hops_remaining == 0 {
+            self.hop_limit_drops += 1;
+            warn!("{:?} Hop limit reached - Dropping message from {:?} to {:?}",
+                  self,
+                  signed_msg.content().src(),
+                  signed_msg.content().dst());
+            return self.handle_if_close(signed_msg, hop_content, hops_remaining, trace);
+        }
+        // Only routing nodes have alternative next hops to resend to.
+        let ack_requested = self.config.hop_acks && self.state == State::Node;
+        // Clients are not part of the route.
+        let mut hop_trace = trace.clone();
+        if self.state == State::Node {
+            if let Some(ref mut route) = hop_trace {
+                let hop = try!(TracedHop::new(signed_msg, route.last(), &self.full_id));
+                route.push(hop);
+            }
+        }
+        let hop_msg = try!(HopMessage::new(hop_content.clone(),
                                            self.full_id.public_id().name().clone(),
+                                           ack_requested,
+                                           hops_remaining - 1,
+                                           hop_trace,
                                            self.full_id.signing_private_key()));
         let message = Message::HopMessage(hop_msg);
         let raw_bytes = try!(serialisation::serialise(&message));
+        if raw_bytes.len() > self.config.max_hop_message_size {
+            return Err(RoutingError::Interface(InterfaceError::MessageTooLarge {
+                size: raw_bytes.len(),
+                max_size: self.config.max_hop_message_size,
+            }));
+        }
+
+        let priority = Priority::of(signed_msg.content());
 
         // If we're a client going to be a node, send via our bootstrap connection
         if self.state == State::Client {
             if let Authority::Client { ref proxy_node_name, .. } = *signed_msg.content().src() {
-                if let Some((connection, _)) = self.proxy_map
-                                                   .iter()
-                                                   .find(|elt| elt.1.name() == proxy_node_name) {
-                    return Ok(self.crust_service.send(connection.clone(), raw_bytes));
+                let proxy_connection = self.proxy_map
+                                           .iter()
+                                           .find(|elt| elt.1.name() == proxy_node_name)
+                                           .map(|(connection, _)| *connection);
+                if let Some(connection) = proxy_connection {
+                    return self.send_bytes(connection, priority, raw_bytes);
                 }
 
                 error!("{:?} Unable to find connection to proxy node in proxy map",
@@ -1620,23 +3282,174 @@ impl Core {
         }
 
         // Query routing table to send it out parallel or to our close group (ourselves excluded)
-        let targets = self.routing_table.target_nodes(signed_msg.content().dst().get_name());
-        targets.iter().foreach(|node_info| {
-            if let Some(connection) = node_info.connections.iter().next() {
-                self.crust_service.send(connection.clone(), raw_bytes.clone());
+        let dst = signed_msg.content().dst();
+        let mut targets = self.routing_table.target_nodes(dst.get_name());
+        if self.config.latency_aware_forwarding && !dst.is_group() &&
+           !self.routing_table.is_close(dst.get_name()) {
+            targets = self.latency_aware_targets(dst.get_name(), targets.len());
+        }
+        let digest = *hop_content.digest();
+        let now = SteadyTime::now();
+        // If a next hop's queue is full, the message is still sent to the others, but the caller
+        // is asked to back off.
+        let mut send_result = Ok(());
+        for node_info in &targets {
+            if let Some(connection) = self.fastest_connection(node_info) {
+                if let Err(error) = self.send_bytes(connection, priority, raw_bytes.clone()) {
+                    send_result = Err(error);
+                    continue;
+                }
+                if ack_requested &&
+                   !self.pending_acks.sent(digest,
+                                           || (raw_bytes.clone(), *dst.get_name(), priority),
+                                           *node_info.public_id.name(),
+                                           now) {
+                    debug!("{:?} Too many messages awaiting acknowledgements - Not tracking {:?}",
+                           self,
+                           digest);
+                }
             }
-        });
+        }
+
+        try!(self.handle_if_close(signed_msg, hop_content, hops_remaining, trace));
+        send_result
+    }
 
-        // If we need to handle this message, handle it.
+    // If we need to handle this message, handle it.
+    fn handle_if_close(&mut self,
+                       signed_msg: &SignedMessage,
+                       hop_content: &HopContent,
+                       hops_remaining: u8,
+                       trace: &Option<Vec<TracedHop>>)
+                       -> Result<(), RoutingError> {
+        let our_name = *self.full_id.public_id().name();
+        let our_key = *self.full_id.public_id().signing_public_key();
         if self.routing_table.is_close(signed_msg.content().dst().get_name()) &&
-           self.signed_message_filter.insert(signed_msg.clone()).is_none() {
-            let hop_name = self.full_id.public_id().name().clone();
-            return self.handle_signed_message_for_node(&signed_msg, &hop_name);
+           self.signed_message_filter.insert(our_key, *hop_content.digest()) {
+            return self.handle_signed_message_for_node(signed_msg,
+                                                       hop_content,
+                                                       &our_name,
+                                                       our_key,
+                                                       hops_remaining,
+                                                       trace);
         }
 
         Ok(())
     }
 
+    // Returns the `count` nodes closest to `target`, except that among the nodes that are equally
+    // close, i. e. in the same bucket with respect to `target`, the ones with the lowest round trip
+    // time are preferred.
+    fn latency_aware_targets(&self,
+                             target: &XorName,
+                             count: usize)
+                             -> Vec<NodeInfo<PublicId, crust::Connection>> {
+        let candidates = self.routing_table
+                             .closest_nodes_to(target, self.routing_table.len(), false);
+        let cutoff_index = match candidates.get(count.saturating_sub(1)) {
+            Some(node_info) => utils::bucket_index(target, node_info.public_id.name()),
+            None => return candidates,
+        };
+        let (mut targets, mut equally_close): (Vec<_>, Vec<_>) =
+            candidates.into_iter()
+                      .filter(|node_info| {
+                          utils::bucket_index(target, node_info.public_id.name()) >= cutoff_index
+                      })
+                      .partition(|node_info| {
+                          utils::bucket_index(target, node_info.public_id.name()) > cutoff_index
+                      });
+        // Nodes without a measured round trip time come last.
+        equally_close.sort_by_key(|node_info| {
+            self.node_round_trip_time(node_info)
+                .map_or((1, 0), |rtt| (0, rtt.num_milliseconds()))
+        });
+        let remaining = count - targets.len();
+        targets.extend(equally_close.into_iter().take(remaining));
+        targets
+    }
+
+    // Returns the node's connection with the lowest round trip time, or the first one if the round
+    // trip times are unknown.
+    fn fastest_connection(&self,
+                          node_info: &NodeInfo<PublicId, crust::Connection>)
+                          -> Option<crust::Connection> {
+        node_info.connections
+                 .iter()
+                 .min_by_key(|connection| {
+                     self.liveness
+                         .round_trip_time(connection)
+                         .map_or((1, 0), |rtt| (0, rtt.num_milliseconds()))
+                 })
+                 .cloned()
+    }
+
+    fn node_round_trip_time(&self,
+                            node_info: &NodeInfo<PublicId, crust::Connection>)
+                            -> Option<Duration> {
+        node_info.connections
+                 .iter()
+                 .filter_map(|connection| self.liveness.round_trip_time(connection))
+                 .min()
+    }
+
+    // Resends the relayed messages that haven't been acknowledged in time to an alternative next
+    // hop: another member of the destination's close group if we are in it, and otherwise another
+    // node closer to the destination than ourselves.
+    fn resend_unacknowledged(&mut self) {
+        for timeout in self.pending_acks.expire(SteadyTime::now()) {
+            let Timeout { key, peer, message: (raw_bytes, dst, priority), tried, resends } =
+                timeout;
+            if resends >= self.config.hop_ack_max_resends {
+                warn!("{:?} Message to {:?} not acknowledged by {:?} - Giving up after {} resends",
+                      self,
+                      dst,
+                      peer,
+                      resends);
+                continue;
+            }
+            let our_name = *self.full_id.public_id().name();
+            let candidates = if self.routing_table.is_close(&dst) {
+                self.routing_table.target_nodes(&dst)
+            } else {
+                self.routing_table
+                    .closest_nodes_to(&dst, self.routing_table.len(), false)
+                    .into_iter()
+                    .filter(|node_info| {
+                        ::xor_name::closer_to_target(node_info.public_id.name(), &our_name, &dst)
+                    })
+                    .collect()
+            };
+            let alternative = candidates.into_iter().find(|node_info| {
+                !tried.contains(node_info.public_id.name())
+            });
+            match alternative.and_then(|node_info| {
+                self.fastest_connection(&node_info).map(|connection| (node_info, connection))
+            }) {
+                Some((node_info, connection)) => {
+                    debug!("{:?} Message to {:?} not acknowledged by {:?} - Resending via {:?}",
+                           self,
+                           dst,
+                           peer,
+                           node_info.public_id.name());
+                    if let Err(error) = self.send_bytes(connection, priority, raw_bytes) {
+                        debug!("{:?} Failed to resend message: {:?}", self, error);
+                        continue;
+                    }
+                    self.pending_acks.resent(&key,
+                                             *node_info.public_id.name(),
+                                             SteadyTime::now());
+                }
+                None => {
+                    warn!("{:?} Message to {:?} not acknowledged by {:?} - No alternative next \
+                           hop",
+                          self,
+                          dst,
+                          peer);
+                }
+            }
+        }
+    }
+
     fn get_client_authority(&self) -> Result<Authority, RoutingError> {
         match self.proxy_map.iter().next() {
             Some((ref _connection, ref bootstrap_pub_id)) => {
@@ -1663,11 +3476,18 @@ impl Core {
     fn dropped_client_connection(&mut self, connection: &crust::Connection) {
         if let Some(public_key) = self.client_map
                                       .iter()
-                                      .find(|entry| (entry.1).0 == *connection)
+                                      .find(|entry| entry.1.connection == *connection)
                                       .map(|entry| entry.0.clone()) {
-            if let Some((_, false)) = self.client_map.remove(&public_key) {
+            if self.client_map
+                   .remove(&public_key)
+                   .map_or(false, |info| !info.client_restriction) {
                 trace!("Joining node dropped. {} remaining.", self.joining_nodes_num());
+                self.join_queue.finish_join(connection);
+                self.admit_queued_joining_nodes();
             }
+        } else if self.join_queue.remove(connection).is_some() {
+            trace!("Queued joining node dropped.");
+            self.admit_queued_joining_nodes();
         }
     }
 
@@ -1714,3 +3534,115 @@ impl Debug for Core {
                self.full_id.public_id().name())
     }
 }
+
+// A filter of the digests of the messages we have handled, with the capacity and the share of each
+// peer given by `config`.
+fn message_filter(config: &Config) -> DigestFilter {
+    DigestFilter::new(Duration::minutes(20),
+                      config.message_filter_capacity,
+                      config.message_filter_peer_share)
+}
+
+// The accumulator of the signatures of group messages, with the capacity and the share of each
+// signer given by `config`. The quorum size is set once it is known.
+fn message_accumulator(config: &Config) -> SignatureAccumulator {
+    SignatureAccumulator::new(1,
+                              Duration::minutes(5),
+                              config.accumulator_capacity,
+                              config.accumulator_peer_share)
+}
+
+#[cfg(test)]
+mod test {
+    use authority::Authority;
+    use config::Config;
+    use id::FullId;
+    use maidsafe_utilities::serialisation::{deserialise, serialise};
+    use messages::{HopContent, ResponseContent, ResponseMessage, RoutingMessage, SignedMessage};
+    use rand;
+    use sodiumoxide::crypto::hash::sha512;
+    use types::MessageId;
+    use xor_name::XorName;
+    use super::{message_accumulator, message_filter};
+
+    // Signs the message and passes it through serialisation, as it arrives at a node.
+    fn receive(response: &ResponseMessage, full_id: &FullId) -> (HopContent, SignedMessage) {
+        let routing_msg = RoutingMessage::Response(response.clone());
+        let signed_msg = unwrap_result!(SignedMessage::new(routing_msg, full_id));
+        let bytes = unwrap_result!(serialise(&unwrap_result!(HopContent::new(&signed_msg))));
+        let hop_content: HopContent = unwrap_result!(deserialise(&bytes));
+        let signed_msg = unwrap_result!(hop_content.signed_message());
+        (hop_content, signed_msg)
+    }
+
+    fn put_success(group_name: XorName) -> ResponseMessage {
+        ResponseMessage {
+            src: Authority::NaeManager(group_name),
+            dst: Authority::ManagedNode(rand::random()),
+            content: ResponseContent::PutSuccess(sha512::hash(&[]), MessageId::new()),
+        }
+    }
+
+    #[test]
+    fn filters_under_sustained_load() {
+        let config = Config {
+            message_filter_capacity: 200,
+            message_filter_peer_share: 50,
+            accumulator_capacity: 50,
+            accumulator_peer_share: 10,
+            ..Config::default()
+        };
+        let mut signed_message_filter = message_filter(&config);
+        let mut grp_msg_filter = message_filter(&config);
+        let mut accumulator = message_accumulator(&config);
+        accumulator.set_quorum_size(3);
+
+        // The first of three group members sends its message via an honest peer.
+        let group = (0..3).map(|_| FullId::new()).collect::<Vec<_>>();
+        let honest_peer = *FullId::new().public_id().signing_public_key();
+        let response = put_success(rand::random());
+        let (hop_content, signed_msg) = receive(&response, &group[0]);
+        assert!(signed_message_filter.insert(honest_peer, *hop_content.digest()));
+        assert!(accumulator.add(*signed_msg.content_digest(),
+                                signed_msg.public_id().clone(),
+                                *signed_msg.signature(),
+                                honest_peer)
+                           .is_none());
+
+        // A single peer floods us with unique group messages, each signed with a new key.
+        let flooding_peer = *FullId::new().public_id().signing_public_key();
+        for _ in 0..(5 * config.message_filter_capacity) {
+            let (hop_content, signed_msg) = receive(&put_success(rand::random()), &FullId::new());
+            assert!(signed_message_filter.insert(flooding_peer, *hop_content.digest()));
+            assert!(accumulator.add(*signed_msg.content_digest(),
+                                    signed_msg.public_id().clone(),
+                                    *signed_msg.signature(),
+                                    flooding_peer)
+                               .is_none());
+        }
+        assert!(signed_message_filter.early_evictions() > 0);
+        assert!(accumulator.early_evictions() > 0);
+
+        // The honest message is still recognised when replayed.
+        assert!(!signed_message_filter.insert(flooding_peer, *hop_content.digest()));
+
+        // The other members' messages still complete the accumulation.
+        let (hop_content, signed_msg) = receive(&response, &group[1]);
+        assert!(signed_message_filter.insert(honest_peer, *hop_content.digest()));
+        assert!(accumulator.add(*signed_msg.content_digest(),
+                                signed_msg.public_id().clone(),
+                                *signed_msg.signature(),
+                                honest_peer)
+                           .is_none());
+        let (hop_content, signed_msg) = receive(&response, &group[2]);
+        assert!(signed_message_filter.insert(honest_peer, *hop_content.digest()));
+        let digest = *signed_msg.content_digest();
+        assert!(accumulator.add(digest,
+                                signed_msg.public_id().clone(),
+                                *signed_msg.signature(),
+                                honest_peer)
+                           .is_some());
+        assert!(grp_msg_filter.insert(honest_peer, digest));
+        assert!(grp_msg_filter.contains(&digest));
+    }
+}
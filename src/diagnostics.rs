@@ -0,0 +1,80 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use id::PublicId;
+use std::time::Duration;
+use xor_name::XorName;
+
+/// The state of a node's connection to the network.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ConnectionState {
+    /// Not connected to any node.
+    Disconnected,
+    /// Connected to a proxy node, but not yet accepted by it.
+    Bootstrapping,
+    /// Connected to the network via a proxy node.
+    Client,
+    /// A full routing node.
+    Node,
+}
+
+/// An entry in a node's routing table.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RoutingTableEntry {
+    /// The node's keys and name.
+    pub public_id: PublicId,
+    /// The index of the bucket the node is in, i. e. the number of leading bits its name has in
+    /// common with ours.
+    pub bucket_index: usize,
+    /// The number of connections we have to the node.
+    pub connections: usize,
+    /// Whether the node is in our close group.
+    pub is_close: bool,
+    /// The lowest estimated round trip time of our connections to the node, if it has been
+    /// measured yet.
+    pub round_trip_time: Option<Duration>,
+}
+
+/// A snapshot of a node's routing table and connections.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RoutingTableSnapshot {
+    /// Our own name.
+    pub our_name: XorName,
+    /// Our connection state.
+    pub state: ConnectionState,
+    /// The routing table entries, ordered by closeness to our name.
+    pub entries: Vec<RoutingTableEntry>,
+    /// The number of proxy nodes we are connected to the network through.
+    pub proxy_count: usize,
+    /// The number of clients we are the proxy node for.
+    pub client_count: usize,
+    /// The number of joining nodes we are the proxy node for.
+    pub joining_node_count: usize,
+    /// The number of joining nodes waiting for us to become their proxy node.
+    pub queued_joining_node_count: usize,
+    /// The estimated number of nodes in the network.
+    pub network_size_estimate: u64,
+    /// The number of messages we dropped because they reached their hop limit.
+    pub hop_limit_drops: u64,
+    /// The number of messages we dropped because the send queue to a peer was full.
+    pub send_queue_drops: u64,
+    /// The number of message digests our filters forgot before they expired, so that replayed
+    /// messages may not be recognised.
+    pub message_filter_evictions: u64,
+    /// The number of group messages or signatures we forgot before they expired, so that the
+    /// messages may never reach the quorum.
+    pub accumulator_evictions: u64,
+}
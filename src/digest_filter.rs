@@ -0,0 +1,221 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use lru_time_cache::LruCache;
+use sodiumoxide::crypto::hash::sha512;
+use sodiumoxide::crypto::sign;
+use std::collections::VecDeque;
+use time::Duration;
+
+/// Remembers the digests of the messages seen recently.
+///
+/// Only the digests are stored, so the memory used doesn't depend on the size of the messages. A
+/// digest is forgotten after the expiry duration, or when the capacity is exceeded, whichever comes
+/// first. Each peer's messages can only occupy a share of the capacity, so that a peer flooding us
+/// with unique messages forgets its own digests early rather than everyone else's. Peers are
+/// identified by the keys of the connections we received the messages on, not by their claimed
+/// originators, which are chosen by the sender.
+pub struct DigestFilter {
+    digests: LruCache<sha512::Digest, ()>,
+    capacity: usize,
+    peer_shares: PeerShares,
+    early_evictions: u64,
+}
+
+impl DigestFilter {
+    /// Create a filter that remembers up to `capacity` digests, each for the `expiry` duration, and
+    /// up to `peer_share` digests of the messages from a single peer.
+    pub fn new(expiry: Duration, capacity: usize, peer_share: usize) -> DigestFilter {
+        DigestFilter {
+            digests: LruCache::with_expiry_duration_and_capacity(expiry, capacity),
+            capacity: capacity,
+            peer_shares: PeerShares::new(expiry, capacity, peer_share),
+            early_evictions: 0,
+        }
+    }
+
+    /// Add the digest of a message received from the peer with the signing key `peer`, and return
+    /// `false` if it was already present.
+    pub fn insert(&mut self, peer: sign::PublicKey, digest: sha512::Digest) -> bool {
+        if self.digests.contains_key(&digest) {
+            let _ = self.digests.insert(digest, ());
+            return false;
+        }
+        let oldest = {
+            let digests = &self.digests;
+            self.peer_shares.add(peer, digest, |digest| digests.contains_key(digest))
+        };
+        if let Some(oldest) = oldest {
+            if self.digests.remove(&oldest).is_some() {
+                self.early_evictions += 1;
+            }
+        }
+        if self.digests.len() >= self.capacity {
+            debug!("Message filter is full, forgetting the oldest digest before it expires.");
+            self.early_evictions += 1;
+        }
+        let _ = self.digests.insert(digest, ());
+        true
+    }
+
+    /// Whether the digest is present.
+    pub fn contains(&self, digest: &sha512::Digest) -> bool {
+        self.digests.contains_key(digest)
+    }
+
+    /// The number of digests forgotten before they expired, to stay within the capacity or a
+    /// peer's share of it.
+    pub fn early_evictions(&self) -> u64 {
+        self.early_evictions
+    }
+}
+
+/// Keeps track of which peer's messages added the entries of a bounded cache, so that a single
+/// peer can't occupy more than its share of it.
+pub struct PeerShares {
+    share: usize,
+    // The digests added by each peer, oldest first. A peer is forgotten after the expiry
+    // duration of the cache, when its entries have expired, too, or when more peers than the
+    // cache's capacity have added entries since.
+    digests: LruCache<sign::PublicKey, VecDeque<sha512::Digest>>,
+}
+
+impl PeerShares {
+    /// Create a tracker allowing `share` entries per peer, for a cache that holds up to `capacity`
+    /// entries, which expire after the `expiry` duration.
+    pub fn new(expiry: Duration, capacity: usize, share: usize) -> PeerShares {
+        PeerShares {
+            share: share,
+            digests: LruCache::with_expiry_duration_and_capacity(expiry, capacity),
+        }
+    }
+
+    /// Record that `peer` added the entry with the given digest. If this exceeds the peer's share,
+    /// return the digest of its oldest entry, which should be removed from the cache.
+    ///
+    /// Once the peer has reached its share, its entries for which `is_cached` returns `false`,
+    /// because the cache has evicted them in the meantime, are forgotten first.
+    pub fn add<F>(&mut self,
+                  peer: sign::PublicKey,
+                  digest: sha512::Digest,
+                  is_cached: F)
+                  -> Option<sha512::Digest>
+        where F: Fn(&sha512::Digest) -> bool
+    {
+        let mut oldest = None;
+        let mut new_peer = false;
+        if let Some(digests) = self.digests.get_mut(&peer) {
+            if digests.len() >= self.share {
+                digests.retain(|digest| is_cached(digest));
+            }
+            digests.push_back(digest);
+            if digests.len() > self.share {
+                oldest = digests.pop_front();
+            }
+        } else {
+            new_peer = true;
+        }
+        if new_peer {
+            let mut digests = VecDeque::new();
+            digests.push_back(digest);
+            let _ = self.digests.insert(peer, digests);
+        }
+        oldest
+    }
+
+    /// Forget that `peer` added the entry with the given digest, because it has been removed from
+    /// the cache.
+    pub fn remove(&mut self, peer: &sign::PublicKey, digest: &sha512::Digest) {
+        let is_empty = match self.digests.get_mut(peer) {
+            Some(digests) => {
+                digests.retain(|added| added != digest);
+                digests.is_empty()
+            }
+            None => return,
+        };
+        if is_empty {
+            let _ = self.digests.remove(peer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DigestFilter, PeerShares};
+    use sodiumoxide::crypto::hash::sha512;
+    use sodiumoxide::crypto::sign;
+    use time::Duration;
+
+    #[test]
+    fn bounded_under_sustained_load() {
+        let capacity = 100;
+        let mut filter = DigestFilter::new(Duration::minutes(20), capacity, capacity);
+        let peer = sign::gen_keypair().0;
+        for i in 0..(10 * capacity) {
+            let digest = sha512::hash(i.to_string().as_bytes());
+            assert!(filter.insert(peer, digest));
+            assert!(!filter.insert(peer, digest));
+            assert!(filter.digests.len() <= capacity);
+        }
+        // The most recent digests are still filtered, and the oldest have been forgotten.
+        assert!(filter.contains(&sha512::hash((10 * capacity - 1).to_string().as_bytes())));
+        assert!(!filter.contains(&sha512::hash(0.to_string().as_bytes())));
+        assert_eq!(9 * capacity as u64, filter.early_evictions());
+    }
+
+    #[test]
+    fn flooding_peer_evicts_its_own_digests() {
+        let capacity = 100;
+        let mut filter = DigestFilter::new(Duration::minutes(20), capacity, capacity / 2);
+        let honest_peer = sign::gen_keypair().0;
+        let flooding_peer = sign::gen_keypair().0;
+        let honest_digest = sha512::hash(b"honest");
+        assert!(filter.insert(honest_peer, honest_digest));
+        for i in 0..(10 * capacity) {
+            assert!(filter.insert(flooding_peer, sha512::hash(i.to_string().as_bytes())));
+            assert!(filter.digests.len() <= capacity / 2 + 1);
+        }
+        assert!(!filter.insert(flooding_peer, honest_digest));
+        assert!(filter.contains(&sha512::hash((10 * capacity - 1).to_string().as_bytes())));
+        assert_eq!((10 * capacity - capacity / 2) as u64, filter.early_evictions());
+    }
+
+    #[test]
+    fn bounded_number_of_peers() {
+        let capacity = 100;
+        let mut filter = DigestFilter::new(Duration::minutes(20), capacity, capacity);
+        for i in 0..(10 * capacity) {
+            assert!(filter.insert(sign::gen_keypair().0, sha512::hash(i.to_string().as_bytes())));
+            assert!(filter.peer_shares.digests.len() <= capacity);
+        }
+    }
+
+    #[test]
+    fn evicted_digests_leave_the_share() {
+        let mut shares = PeerShares::new(Duration::minutes(20), 10, 2);
+        let peer = sign::gen_keypair().0;
+        let digests = (0..3).map(|i: u8| sha512::hash(&[i])).collect::<Vec<_>>();
+        assert_eq!(None, shares.add(peer, digests[0], |_| true));
+        assert_eq!(None, shares.add(peer, digests[1], |_| true));
+        // The first digest is still cached, so it is the oldest one over the share.
+        assert_eq!(Some(digests[0]), shares.add(peer, digests[2], |_| true));
+        // Once the cache has evicted the remaining ones, they don't count towards the share.
+        assert_eq!(None, shares.add(peer, digests[0], |_| false));
+        shares.remove(&peer, &digests[0]);
+        assert_eq!(None, shares.add(peer, digests[1], |_| true));
+    }
+}
@@ -29,6 +29,22 @@ pub enum InterfaceError {
     ChannelRxError(RecvError),
     /// Error while trying to transmit an event via a channel
     EventSenderError(EventSenderError<MaidSafeEventCategory, Action>),
+    /// The data is larger than the configured maximum for its type
+    DataTooLarge {
+        /// The data's payload size in bytes
+        size: usize,
+        /// The maximum payload size in bytes
+        max_size: usize,
+    },
+    /// The serialised message is larger than the configured maximum
+    MessageTooLarge {
+        /// The message's size in bytes
+        size: usize,
+        /// The maximum message size in bytes
+        max_size: usize,
+    },
+    /// The queue of messages waiting to be sent to a peer is full; try again later
+    SendQueueFull,
 }
 
 impl From<EventSenderError<MaidSafeEventCategory, Action>> for InterfaceError {
@@ -103,6 +119,20 @@ pub enum RoutingError {
     ClientConnectionNotFound,
     /// Invalid Source
     InvalidSource,
+    /// Relocated name does not match the original name and close nodes it was computed from
+    InvalidRelocation,
+    /// Resource proof from a joining node is missing, unexpected or invalid
+    InvalidResourceProof,
+    /// A client sent more messages or bytes than its rate limit allows
+    RateLimitExceeded,
+    /// Received a message or data larger than the configured maximum
+    MessageTooLarge,
+    /// The configuration parameters are inconsistent
+    InvalidConfig,
+    /// A traced message's route is missing, unexpected or doesn't end with the previous hop
+    InvalidTrace,
+    /// Too many bytes received on the connection are still waiting to be checked
+    QueueFull,
 }
 
 impl From<::std::str::Utf8Error> for RoutingError {
@@ -15,9 +15,25 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use authority::Authority;
+use crust::Endpoint;
+use sodiumoxide::crypto::sign;
+use std::time::Duration;
 use xor_name::XorName;
 use types::MessageId;
-use messages::{RequestMessage, ResponseMessage};
+use id::PublicId;
+use messages::{GroupProof, RequestMessage, ResponseMessage};
+
+/// A stage in the process of a node joining the network.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JoinStage {
+    /// Waiting for the `NaeManager` to assign a new name.
+    Relocating,
+    /// Waiting for the new close group to accept the resource proofs and reveal its members.
+    GettingCloseGroup,
+    /// Waiting for the first connection to a member of the close group.
+    Connecting,
+}
 
 /// An Event raised by a `Node` or `Client` via its event sender.
 ///
@@ -25,13 +41,18 @@ use messages::{RequestMessage, ResponseMessage};
 /// responses, and to react to changes in the network.
 ///
 /// `Request` and `Response` events from group authorities are only raised once the quorum has been
-/// reached, i. e. enough members of the group have sent the same message.
+/// reached, i. e. enough members of the group have sent the same message. `Response` events from
+/// group authorities also carry the signatures of those members, which can be checked using
+/// `ResponseMessage::verify_group_proof`. That only shows that the signatures are from distinct
+/// keys; a proxy node could have generated all of them. Where it matters, callers need to check
+/// that the signers are members of the source group, using `ResponseMessage::verify_group_members`
+/// with the group's `PublicId`s, e. g. as obtained by `Client::send_get_group_ids_request`.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Event {
     /// Request.
     Request(RequestMessage),
-    /// Response.
-    Response(ResponseMessage),
+    /// Response, and the group members' signatures if it was sent by a group authority.
+    Response(ResponseMessage, Option<GroupProof>),
     /// A churn event: a node left or joined this node's close group.
     Churn {
         /// The unique ID of this `Churn` event.
@@ -41,4 +62,52 @@ pub enum Event {
     },
     /// The client has successfully connected to a proxy node on the network.
     Connected,
+    /// The proxy node is busy and has put us in its queue of joining nodes.
+    ///
+    /// This can be raised repeatedly as the queue advances, until the node is accepted.
+    JoinQueued {
+        /// Our position in the queue, starting at 1.
+        position: usize,
+        /// The proxy's estimate of the time until we will be accepted.
+        estimated_wait: Duration,
+    },
+    /// Joining the network stalled at the given stage, and is restarted from bootstrapping.
+    JoinFailed(JoinStage),
+    /// A lookup started with `Node::find_nodes` has completed.
+    LookupComplete {
+        /// The ID the lookup was started with.
+        id: MessageId,
+        /// The name the nodes are closest to.
+        target: XorName,
+        /// The closest nodes found, ordered by closeness to `target`. Each of them has responded
+        /// to the lookup with a message signed by the keys in its `PublicId`, but their names are
+        /// not verified: a node could have been reported, and respond, under a name it wasn't
+        /// relocated to.
+        unverified_nodes: Vec<PublicId>,
+    },
+    /// A peer has repeatedly violated the protocol and has been disconnected and temporarily
+    /// banned.
+    PeerBanned {
+        /// The peer's signing key, if it had identified itself.
+        public_key: Option<sign::PublicKey>,
+        /// The peer's endpoint. Its IP address is banned.
+        endpoint: Endpoint,
+    },
+    /// The proxy node dropped one of our messages because we exceeded our rate limit.
+    RateLimitExceeded {
+        /// The time after which the message would have been accepted, or `None` if it exceeds the
+        /// limits on its own.
+        retry_after: Option<Duration>,
+    },
+    /// A message we sent with `Node::send_traced` or `Client::send_traced_request` has reached its
+    /// destination, whose member closest to the destination name has returned the route it took.
+    RouteTraced {
+        /// The ID of the traced message, if it has one.
+        id: Option<MessageId>,
+        /// The destination authority of the traced message.
+        dst: Authority,
+        /// The names of the nodes the message passed through, starting with ourselves unless we
+        /// are a client, and ending with the recipient. Each of them has signed the message.
+        route: Vec<XorName>,
+    },
 }
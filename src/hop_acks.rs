@@ -0,0 +1,211 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use time::{Duration, SteadyTime};
+
+struct PendingMessage<P, M> {
+    message: M,
+    // The peers we are waiting for an acknowledgement from, and when we sent the message to them
+    awaiting: HashMap<P, SteadyTime>,
+    // All peers the message has been sent to
+    tried: HashSet<P>,
+    resends: usize,
+}
+
+/// A peer that didn't acknowledge a message in time.
+pub struct Timeout<K, P, M> {
+    /// The message's key.
+    pub key: K,
+    /// The peer that didn't acknowledge the message.
+    pub peer: P,
+    /// The message.
+    pub message: M,
+    /// All peers the message has been sent to, including `peer`.
+    pub tried: Vec<P>,
+    /// The number of times the message has been resent to an alternative peer.
+    pub resends: usize,
+}
+
+/// Keeps track of messages sent to next hops that have not been acknowledged yet.
+///
+/// At most `capacity` messages are tracked at the same time.
+pub struct PendingAcks<K, P, M> {
+    messages: HashMap<K, PendingMessage<P, M>>,
+    timeout: Duration,
+    capacity: usize,
+}
+
+impl<K, P, M> PendingAcks<K, P, M>
+    where K: Clone + Eq + Hash,
+          P: Clone + Eq + Hash,
+          M: Clone
+{
+    /// Create a tracker that considers a message lost if it isn't acknowledged within `timeout`,
+    /// and tracks up to `capacity` messages.
+    pub fn new(timeout: Duration, capacity: usize) -> PendingAcks<K, P, M> {
+        PendingAcks {
+            messages: HashMap::new(),
+            timeout: timeout,
+            capacity: capacity,
+        }
+    }
+
+    /// Record that the message with the given key has been sent to `peer`. The message is only
+    /// constructed if it isn't tracked yet.
+    ///
+    /// Returns `false` if the message is not tracked because the capacity has been reached.
+    pub fn sent<F>(&mut self, key: K, make_message: F, peer: P, now: SteadyTime) -> bool
+        where F: FnOnce() -> M
+    {
+        if self.messages.len() >= self.capacity && !self.messages.contains_key(&key) {
+            return false;
+        }
+        let pending = self.messages.entry(key).or_insert_with(|| {
+            PendingMessage {
+                message: make_message(),
+                awaiting: HashMap::new(),
+                tried: HashSet::new(),
+                resends: 0,
+            }
+        });
+        let _ = pending.awaiting.insert(peer.clone(), now);
+        let _ = pending.tried.insert(peer);
+        true
+    }
+
+    /// Record that the message with the given key has been resent to the alternative `peer`.
+    pub fn resent(&mut self, key: &K, peer: P, now: SteadyTime) {
+        if let Some(pending) = self.messages.get_mut(key) {
+            pending.resends += 1;
+            let _ = pending.awaiting.insert(peer.clone(), now);
+            let _ = pending.tried.insert(peer);
+        }
+    }
+
+    /// Record that `peer` has acknowledged the message with the given key.
+    pub fn acked(&mut self, key: &K, peer: &P) {
+        let done = match self.messages.get_mut(key) {
+            Some(pending) => {
+                let _ = pending.awaiting.remove(peer);
+                pending.awaiting.is_empty()
+            }
+            None => false,
+        };
+        if done {
+            let _ = self.messages.remove(key);
+        }
+    }
+
+    /// Return the peers that haven't acknowledged a message in time, and stop waiting for them.
+    ///
+    /// A message is forgotten once there are no peers left to wait for, unless it is resent
+    /// before the next call.
+    pub fn expire(&mut self, now: SteadyTime) -> Vec<Timeout<K, P, M>> {
+        self.messages.retain(|_, pending| !pending.awaiting.is_empty());
+        let timeout = self.timeout;
+        let mut timeouts = Vec::new();
+        for (key, pending) in &mut self.messages {
+            let expired = pending.awaiting
+                                 .iter()
+                                 .filter(|&(_, &sent_at)| now - sent_at >= timeout)
+                                 .map(|(peer, _)| peer.clone())
+                                 .collect::<Vec<_>>();
+            for peer in expired {
+                let _ = pending.awaiting.remove(&peer);
+                timeouts.push(Timeout {
+                    key: key.clone(),
+                    peer: peer,
+                    message: pending.message.clone(),
+                    tried: pending.tried.iter().cloned().collect(),
+                    resends: pending.resends,
+                });
+            }
+        }
+        timeouts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PendingAcks;
+    use time::{Duration, SteadyTime};
+
+    #[test]
+    fn acknowledged_messages_are_forgotten() {
+        let mut pending_acks = PendingAcks::new(Duration::seconds(5), 10);
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+
+        assert!(pending_acks.sent(1, || "one", 10, at(0)));
+        assert!(pending_acks.sent(1, || unreachable!(), 11, at(0)));
+        assert!(pending_acks.sent(2, || "two", 10, at(0)));
+        pending_acks.acked(&1, &10);
+        pending_acks.acked(&2, &10);
+        // Acknowledgements for unknown messages or from other peers are ignored.
+        pending_acks.acked(&3, &10);
+        pending_acks.acked(&1, &12);
+
+        let mut timeouts = pending_acks.expire(at(5));
+        assert_eq!(1, timeouts.len());
+        let timeout = &mut timeouts[0];
+        timeout.tried.sort();
+        assert_eq!((1, 11, "one"), (timeout.key, timeout.peer, timeout.message));
+        assert_eq!((vec![10, 11], 0), (timeout.tried.clone(), timeout.resends));
+        assert!(pending_acks.expire(at(10)).is_empty());
+    }
+
+    #[test]
+    fn resent_messages_time_out_again() {
+        let mut pending_acks = PendingAcks::new(Duration::seconds(5), 10);
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+
+        assert!(pending_acks.sent(1, || "one", 10, at(0)));
+        assert!(pending_acks.expire(at(4)).is_empty());
+        assert_eq!(1, pending_acks.expire(at(5)).len());
+        pending_acks.resent(&1, 11, at(5));
+        assert!(pending_acks.expire(at(9)).is_empty());
+
+        let timeouts = pending_acks.expire(at(10));
+        assert_eq!(1, timeouts.len());
+        assert_eq!((11, 1), (timeouts[0].peer, timeouts[0].resends));
+        assert_eq!(2, timeouts[0].tried.len());
+
+        // Not resent again, so the message is forgotten.
+        assert!(pending_acks.expire(at(20)).is_empty());
+        pending_acks.resent(&1, 12, at(20));
+        assert!(pending_acks.expire(at(30)).is_empty());
+    }
+
+    #[test]
+    fn bounded_capacity() {
+        let mut pending_acks = PendingAcks::new(Duration::seconds(5), 2);
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+
+        assert!(pending_acks.sent(1, || "one", 10, at(0)));
+        assert!(pending_acks.sent(2, || "two", 10, at(0)));
+        assert!(!pending_acks.sent(3, || "three", 10, at(0)));
+        // Messages that are already tracked can still be sent to further peers.
+        assert!(pending_acks.sent(2, || "two", 11, at(0)));
+        pending_acks.acked(&1, &10);
+        assert!(pending_acks.sent(3, || "three", 10, at(1)));
+        assert_eq!(2, pending_acks.expire(at(5)).len());
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use time::{Duration, SteadyTime};
+
+/// The assumed duration of a join, before any join has been observed.
+const INITIAL_JOIN_DURATION_SECS: i64 = 60;
+
+/// The weight of the latest observation in the moving average of join durations, in percent.
+const JOIN_DURATION_WEIGHT: i64 = 25;
+
+/// A bounded queue of nodes waiting to join the network via us as their proxy.
+///
+/// It also keeps track of the time joins take, to estimate how long the nodes in the queue will
+/// have to wait.
+pub struct JoinQueue<K, V> {
+    waiting: VecDeque<(K, V)>,
+    max_len: usize,
+    join_start_times: HashMap<K, SteadyTime>,
+    average_join_duration: Duration,
+}
+
+impl<K: Clone + Eq + Hash, V> JoinQueue<K, V> {
+    /// Create an empty queue that holds at most `max_len` waiting nodes.
+    pub fn new(max_len: usize) -> JoinQueue<K, V> {
+        JoinQueue {
+            waiting: VecDeque::new(),
+            max_len: max_len,
+            join_start_times: HashMap::new(),
+            average_join_duration: Duration::seconds(INITIAL_JOIN_DURATION_SECS),
+        }
+    }
+
+    /// Append a node to the queue and return its position, starting at 1. Returns `None` if the
+    /// queue is full. If the key is already queued, its value is replaced and it keeps its place.
+    pub fn push(&mut self, key: K, value: V) -> Option<usize> {
+        if let Some(position) = self.waiting.iter().position(|&(ref k, _)| *k == key) {
+            self.waiting[position].1 = value;
+            return Some(position + 1);
+        }
+        if self.waiting.len() >= self.max_len {
+            return None;
+        }
+        self.waiting.push_back((key, value));
+        Some(self.waiting.len())
+    }
+
+    /// Remove and return the node that has been waiting the longest.
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        self.waiting.pop_front()
+    }
+
+    /// Remove the node with the given key from the queue, e. g. because it disconnected.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.waiting
+            .iter()
+            .position(|&(ref k, _)| k == key)
+            .and_then(|position| self.waiting.remove(position))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the number of waiting nodes.
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Returns whether no nodes are waiting.
+    pub fn is_empty(&self) -> bool {
+        self.waiting.is_empty()
+    }
+
+    /// Returns the keys of all waiting nodes, in order.
+    pub fn keys(&self) -> Vec<K> {
+        self.waiting.iter().map(|&(ref key, _)| key.clone()).collect()
+    }
+
+    /// Record that the node with the given key has started joining.
+    pub fn start_join(&mut self, key: K) {
+        let _ = self.join_start_times.insert(key, SteadyTime::now());
+    }
+
+    /// Record that the node with the given key has completed or abandoned its join.
+    pub fn finish_join(&mut self, key: &K) {
+        if let Some(start_time) = self.join_start_times.remove(key) {
+            self.record_join_duration(SteadyTime::now() - start_time);
+        }
+    }
+
+    /// Update the moving average of join durations with a new observation.
+    pub fn record_join_duration(&mut self, duration: Duration) {
+        let average_ms = self.average_join_duration.num_milliseconds();
+        let new_ms = (average_ms * (100 - JOIN_DURATION_WEIGHT) +
+                      duration.num_milliseconds() * JOIN_DURATION_WEIGHT) / 100;
+        self.average_join_duration = Duration::milliseconds(new_ms);
+    }
+
+    /// Estimate how long the node at `position` will have to wait, if `parallel_joins` nodes can
+    /// join at the same time.
+    pub fn estimated_wait(&self, position: usize, parallel_joins: usize) -> Duration {
+        let rounds = (position + parallel_joins - 1) / parallel_joins;
+        self.average_join_duration * rounds as i32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::JoinQueue;
+    use time::Duration;
+
+    #[test]
+    fn queue_order() {
+        let mut queue = JoinQueue::new(3);
+        assert!(queue.is_empty());
+        assert_eq!(Some(1), queue.push(1, 'a'));
+        assert_eq!(Some(2), queue.push(2, 'b'));
+        assert_eq!(Some(3), queue.push(3, 'c'));
+        assert_eq!(None, queue.push(4, 'd'));
+        assert_eq!(Some(2), queue.push(2, 'e'));
+        assert_eq!(vec![1, 2, 3], queue.keys());
+        assert_eq!(3, queue.len());
+
+        assert_eq!(Some('e'), queue.remove(&2));
+        assert_eq!(None, queue.remove(&2));
+        assert_eq!(vec![1, 3], queue.keys());
+        assert_eq!(Some(3), queue.push(4, 'd'));
+
+        assert_eq!(Some((1, 'a')), queue.pop());
+        assert_eq!(Some((3, 'c')), queue.pop());
+        assert_eq!(Some((4, 'd')), queue.pop());
+        assert_eq!(None, queue.pop());
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn estimated_wait() {
+        let mut queue = JoinQueue::<u8, ()>::new(10);
+        let initial = Duration::seconds(super::INITIAL_JOIN_DURATION_SECS);
+        assert_eq!(initial, queue.estimated_wait(1, 1));
+        assert_eq!(initial * 3, queue.estimated_wait(3, 1));
+        assert_eq!(initial * 2, queue.estimated_wait(3, 2));
+
+        // The average moves towards the observed durations.
+        for _ in 0..50 {
+            queue.record_join_duration(Duration::seconds(20));
+        }
+        let estimate = queue.estimated_wait(1, 1);
+        assert!(estimate < Duration::seconds(21) && estimate >= Duration::seconds(20));
+
+        queue.record_join_duration(Duration::seconds(100));
+        assert!(queue.estimated_wait(1, 1) > estimate);
+
+        // Unstarted joins are not recorded.
+        let estimate = queue.estimated_wait(1, 1);
+        queue.finish_join(&1);
+        assert_eq!(estimate, queue.estimated_wait(1, 1));
+    }
+}
@@ -157,7 +157,6 @@
 extern crate log;
 #[macro_use]
 extern crate maidsafe_utilities;
-extern crate accumulator;
 extern crate cbor;
 extern crate crust;
 extern crate ip;
@@ -175,29 +174,53 @@ mod acceptors;
 mod action;
 mod authority;
 mod client;
+mod config;
 mod core;
 mod data;
+mod diagnostics;
+mod digest_filter;
 mod error;
 mod event;
+mod hop_acks;
 mod id;
 mod immutable_data;
+mod join_queue;
+mod liveness;
+mod lookup;
 mod messages;
 mod node;
 mod plain_data;
+mod rate_limit;
+mod reputation;
+mod resource_proof;
+mod send_queue;
+mod signature_accumulator;
 mod structured_data;
 mod types;
 mod utils;
+/// Decoding and checking of incoming messages, exposed for the fuzz targets and benchmarks only.
+#[doc(hidden)]
+pub mod verifier;
 
 pub use authority::Authority;
 pub use client::Client;
+pub use config::Config;
 pub use data::{Data, DataRequest};
+pub use diagnostics::{ConnectionState, RoutingTableEntry, RoutingTableSnapshot};
 pub use error::{InterfaceError, RoutingError};
-pub use event::Event;
+pub use event::{Event, JoinStage};
 pub use id::{FullId, PublicId};
 pub use immutable_data::{ImmutableData, ImmutableDataType};
-pub use messages::{RequestContent, RequestMessage, ResponseContent, ResponseMessage,
+pub use messages::{GroupProof, RequestContent, RequestMessage, ResponseContent, ResponseMessage,
                    RoutingMessage, SignedMessage};
 pub use node::Node;
 pub use plain_data::PlainData;
 pub use structured_data::{MAX_STRUCTURED_DATA_SIZE_IN_BYTES, StructuredData};
 pub use types::MessageId;
+
+// The wire types and the helpers below are exposed for the fuzz targets and benchmarks only.
+#[doc(hidden)]
+pub use messages::{BootstrapDenyReason, DirectMessage, HopContent, HopMessage, Message, TracedHop};
+#[doc(hidden)]
+pub use utils::{calculate_relocated_name, decrypt_endpoints, encrypt_endpoints,
+                verify_signed_public_id};
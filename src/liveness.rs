@@ -0,0 +1,216 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use std::collections::HashMap;
+use std::hash::Hash;
+use time::{Duration, SteadyTime};
+
+struct PeerState {
+    last_heard: SteadyTime,
+    last_probe: Option<SteadyTime>,
+    probe_outstanding: bool,
+    unanswered_probes: usize,
+    round_trip_time: Option<Duration>,
+}
+
+/// Keeps track of when we last heard from each connection, to detect dead peers.
+///
+/// A connection that has been quiet for an interval is probed with a heartbeat. If a number of
+/// consecutive heartbeats remain unanswered, it is considered dead. The responses to heartbeats
+/// are used to estimate each connection's round trip time.
+pub struct Liveness<K> {
+    peers: HashMap<K, PeerState>,
+    interval: Duration,
+    miss_threshold: usize,
+    probe_active: bool,
+}
+
+impl<K: Clone + Eq + Hash> Liveness<K> {
+    /// Create a tracker that probes quiet connections every `interval` and considers them dead
+    /// after `miss_threshold` unanswered probes.
+    pub fn new(interval: Duration, miss_threshold: usize) -> Liveness<K> {
+        Liveness {
+            peers: HashMap::new(),
+            interval: interval,
+            miss_threshold: miss_threshold,
+            probe_active: false,
+        }
+    }
+
+    /// Whether to also probe connections that are not quiet, so that the round trip time is
+    /// measured for every connection and not only for the quiet ones.
+    pub fn set_probe_active(&mut self, probe_active: bool) {
+        self.probe_active = probe_active;
+    }
+
+    /// Record that a message has been received on the connection, if it is tracked.
+    pub fn heard_from(&mut self, key: &K, now: SteadyTime) {
+        if let Some(peer) = self.peers.get_mut(key) {
+            peer.last_heard = now;
+            peer.unanswered_probes = 0;
+        }
+    }
+
+    /// Record that a heartbeat response has been received on the connection, and update its round
+    /// trip time estimate if we are waiting for a response.
+    pub fn heard_response(&mut self, key: &K, now: SteadyTime) {
+        self.heard_from(key, now);
+        if let Some(peer) = self.peers.get_mut(key) {
+            if !peer.probe_outstanding {
+                return;
+            }
+            peer.probe_outstanding = false;
+            if let Some(last_probe) = peer.last_probe {
+                let sample = now - last_probe;
+                peer.round_trip_time = Some(match peer.round_trip_time {
+                    Some(rtt) => (rtt * 3 + sample) / 4,
+                    None => sample,
+                });
+            }
+        }
+    }
+
+    /// The estimated round trip time of the connection, if it has answered any heartbeats yet.
+    pub fn round_trip_time(&self, key: &K) -> Option<Duration> {
+        self.peers.get(key).and_then(|peer| peer.round_trip_time)
+    }
+
+    /// Stop tracking the connection.
+    pub fn remove(&mut self, key: &K) {
+        let _ = self.peers.remove(key);
+    }
+
+    /// Update the tracked connections to `keys`, and return the ones that need to be probed now
+    /// and the ones that are considered dead. Dead connections are no longer tracked.
+    pub fn check(&mut self, keys: Vec<K>, now: SteadyTime) -> (Vec<K>, Vec<K>) {
+        self.peers.retain(|key, _| keys.contains(key));
+        let mut probes = Vec::new();
+        let mut dead = Vec::new();
+        let interval = self.interval;
+        let miss_threshold = self.miss_threshold;
+        let probe_active = self.probe_active;
+        for key in keys {
+            let peer = self.peers.entry(key.clone()).or_insert(PeerState {
+                last_heard: now,
+                last_probe: None,
+                probe_outstanding: false,
+                unanswered_probes: 0,
+                round_trip_time: None,
+            });
+            let quiet = now - peer.last_heard >= interval;
+            if !quiet && !probe_active {
+                continue;
+            }
+            if peer.last_probe.map_or(false, |last_probe| now - last_probe < interval) {
+                continue;
+            }
+            if quiet {
+                if peer.unanswered_probes >= miss_threshold {
+                    dead.push(key);
+                    continue;
+                }
+                peer.unanswered_probes += 1;
+            }
+            peer.last_probe = Some(now);
+            peer.probe_outstanding = true;
+            probes.push(key);
+        }
+        for key in &dead {
+            let _ = self.peers.remove(key);
+        }
+        (probes, dead)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Liveness;
+    use time::{Duration, SteadyTime};
+
+    #[test]
+    fn probes_quiet_connections() {
+        let mut liveness = Liveness::new(Duration::seconds(10), 2);
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+
+        assert_eq!((vec![], vec![]), liveness.check(vec![1, 2], at(0)));
+        assert_eq!((vec![], vec![]), liveness.check(vec![1, 2], at(5)));
+
+        // Connection 2 is active, so only 1 needs to be probed.
+        liveness.heard_from(&2, at(5));
+        assert_eq!((vec![1], vec![]), liveness.check(vec![1, 2], at(10)));
+        // No new probe before the interval has passed.
+        assert_eq!((vec![2], vec![]), liveness.check(vec![1, 2], at(15)));
+
+        // Connection 2 answers, 1 doesn't.
+        liveness.heard_from(&2, at(16));
+        assert_eq!((vec![1], vec![]), liveness.check(vec![1, 2], at(20)));
+        liveness.heard_from(&2, at(26));
+        assert_eq!((vec![], vec![1]), liveness.check(vec![1, 2], at(30)));
+
+        // Dead connections are no longer tracked, but reappear if passed in again.
+        assert_eq!((vec![2], vec![]), liveness.check(vec![2], at(36)));
+        assert_eq!((vec![], vec![]), liveness.check(vec![1, 2], at(37)));
+    }
+
+    #[test]
+    fn answered_probes_reset_misses() {
+        let mut liveness = Liveness::new(Duration::seconds(10), 2);
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+
+        let _ = liveness.check(vec![1], at(0));
+        for round in 1..10 {
+            assert_eq!((vec![1], vec![]), liveness.check(vec![1], at(round * 10)));
+            // Without the answer, the connection would be dead after the second round.
+            liveness.heard_from(&1, at(round * 10));
+        }
+
+        // Removed connections are not reported.
+        liveness.remove(&1);
+        liveness.heard_from(&1, at(100));
+        assert_eq!((vec![], vec![]), liveness.check(vec![], at(200)));
+    }
+
+    #[test]
+    fn measures_round_trip_time() {
+        let mut liveness = Liveness::new(Duration::seconds(10), 2);
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+        let at_ms = |millis| start + Duration::milliseconds(millis);
+
+        let _ = liveness.check(vec![1, 2], at(0));
+        assert_eq!(None, liveness.round_trip_time(&1));
+
+        // Only the quiet connection 1 is probed.
+        liveness.heard_from(&2, at(5));
+        assert_eq!((vec![1], vec![]), liveness.check(vec![1, 2], at(10)));
+        liveness.heard_response(&1, at_ms(10_200));
+        assert_eq!(Some(Duration::milliseconds(200)), liveness.round_trip_time(&1));
+        // A response without an outstanding probe is ignored.
+        liveness.heard_response(&1, at_ms(10_900));
+        assert_eq!(Some(Duration::milliseconds(200)), liveness.round_trip_time(&1));
+
+        // With active probing, connection 2 is probed, too, and the estimate is smoothed.
+        liveness.set_probe_active(true);
+        liveness.heard_from(&2, at(19));
+        assert_eq!((vec![1, 2], vec![]), liveness.check(vec![1, 2], at(21)));
+        liveness.heard_response(&1, at_ms(21_600));
+        liveness.heard_response(&2, at_ms(21_100));
+        assert_eq!(Some(Duration::milliseconds(300)), liveness.round_trip_time(&1));
+        assert_eq!(Some(Duration::milliseconds(100)), liveness.round_trip_time(&2));
+    }
+}
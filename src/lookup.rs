@@ -0,0 +1,307 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use id::PublicId;
+use std::collections::{HashMap, HashSet};
+use time::{Duration, SteadyTime};
+use xor_name::{XorName, closer_to_target};
+
+/// The maximum number of queries a lookup has in flight at the same time.
+const PARALLELISM: usize = 3;
+
+/// The state of an iterative search for the nodes closest to a target name.
+///
+/// The lookup starts with the closest nodes we know about, and repeatedly queries the closest
+/// candidates it hasn't queried yet, adding the nodes they return as new candidates. It is
+/// complete once the `count` closest candidates that didn't fail have all responded.
+///
+/// Only nodes that have responded to a query themselves are part of the result: Their responses
+/// are signed, and their `PublicId` must match the one under which they were reported to us.
+/// Their names, however, are only what other nodes reported: nothing shows that they are the
+/// names the nodes were relocated to, so the result is unverified.
+///
+/// Candidates are identified by their full `PublicId`, so that a node reporting fake IDs for other
+/// nodes' names can't displace the real ones: the fake candidates never respond and fail.
+pub struct Lookup {
+    target: XorName,
+    count: usize,
+    // Ordered by closeness to the target, without duplicates
+    candidates: Vec<PublicId>,
+    // The nodes we are awaiting responses from, and the times the queries were sent
+    pending: HashMap<PublicId, SteadyTime>,
+    responded: HashSet<PublicId>,
+    failed: HashSet<PublicId>,
+}
+
+impl Lookup {
+    /// Create a lookup for the `count` nodes closest to `target`, starting with the given nodes.
+    pub fn new(target: XorName, count: usize, initial_nodes: Vec<PublicId>) -> Lookup {
+        let mut lookup = Lookup {
+            target: target,
+            count: count,
+            candidates: Vec::new(),
+            pending: HashMap::new(),
+            responded: HashSet::new(),
+            failed: HashSet::new(),
+        };
+        lookup.add_candidates(initial_nodes);
+        lookup
+    }
+
+    /// Returns the lookup's target name.
+    pub fn target(&self) -> &XorName {
+        &self.target
+    }
+
+    /// Returns the nodes that should be queried next, and marks them as pending.
+    pub fn next_queries(&mut self, now: SteadyTime) -> Vec<PublicId> {
+        let available = PARALLELISM.saturating_sub(self.pending.len());
+        let queries = self.closest_candidates()
+                          .into_iter()
+                          .filter(|public_id| {
+                              !self.responded.contains(public_id) &&
+                              !self.pending.contains_key(public_id)
+                          })
+                          .take(available)
+                          .collect::<Vec<_>>();
+        for public_id in &queries {
+            let _ = self.pending.insert(public_id.clone(), now);
+        }
+        queries
+    }
+
+    /// Add a node that doesn't need to be queried, i. e. ourselves, and the nodes it knows closest
+    /// to the target.
+    pub fn add_response(&mut self, responder: PublicId, nodes: Vec<PublicId>) {
+        let _ = self.responded.insert(responder.clone());
+        self.add_candidates(vec![responder]);
+        self.add_candidates(nodes);
+    }
+
+    /// Handle a response from `responder`, containing the nodes it knows closest to the target.
+    ///
+    /// Returns `false` and ignores the response if it wasn't expected from that node, e. g. if
+    /// the query has expired, or if the responder's keys don't match the queried candidate's.
+    pub fn handle_response(&mut self, responder: &PublicId, nodes: Vec<PublicId>) -> bool {
+        if self.pending.remove(responder).is_none() {
+            return false;
+        }
+        let _ = self.responded.insert(responder.clone());
+        self.add_candidates(nodes);
+        true
+    }
+
+    /// Mark the queries that were sent more than `timeout` before `now` as failed.
+    pub fn expire_queries(&mut self, now: SteadyTime, timeout: Duration) {
+        let expired = self.pending
+                          .iter()
+                          .filter(|&(_, sent_time)| now - *sent_time > timeout)
+                          .map(|(public_id, _)| public_id.clone())
+                          .collect::<Vec<_>>();
+        for public_id in expired {
+            let _ = self.pending.remove(&public_id);
+            let _ = self.failed.insert(public_id);
+        }
+    }
+
+    /// Returns whether the `count` closest candidates that didn't fail have all responded.
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty() &&
+        self.closest_candidates().iter().all(|public_id| self.responded.contains(public_id))
+    }
+
+    /// Returns the closest nodes that have responded, at most `count` of them. Their names are
+    /// not verified.
+    pub fn unverified_result(&self) -> Vec<PublicId> {
+        self.closest_candidates()
+            .into_iter()
+            .filter(|public_id| self.responded.contains(public_id))
+            .collect()
+    }
+
+    // The `count` closest candidates that haven't failed.
+    fn closest_candidates(&self) -> Vec<PublicId> {
+        self.candidates
+            .iter()
+            .filter(|public_id| !self.failed.contains(public_id))
+            .take(self.count)
+            .cloned()
+            .collect()
+    }
+
+    fn add_candidates(&mut self, nodes: Vec<PublicId>) {
+        for public_id in nodes {
+            if !self.candidates.contains(&public_id) {
+                self.candidates.push(public_id);
+            }
+        }
+        let target = self.target;
+        self.candidates.sort_by(|lhs, rhs| {
+            if closer_to_target(lhs.name(), rhs.name(), &target) {
+                ::std::cmp::Ordering::Less
+            } else {
+                ::std::cmp::Ordering::Greater
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate rand;
+
+    use super::Lookup;
+    use id::{FullId, PublicId};
+    use time::{Duration, SteadyTime};
+    use xor_name::{XorName, closer_to_target};
+
+    fn sorted_by_closeness(mut nodes: Vec<PublicId>, target: &XorName) -> Vec<PublicId> {
+        nodes.sort_by(|lhs, rhs| {
+            if closer_to_target(lhs.name(), rhs.name(), target) {
+                ::std::cmp::Ordering::Less
+            } else {
+                ::std::cmp::Ordering::Greater
+            }
+        });
+        nodes
+    }
+
+    // Run the lookup against a network in which every node returns the `2 * count` nodes closest
+    // to the target, except the ones in `silent`, which never respond.
+    fn run(lookup: &mut Lookup, network: &[PublicId], count: usize, silent: &[PublicId]) {
+        let start = SteadyTime::now();
+        let mut rounds = 0;
+        while !lookup.is_complete() {
+            rounds += 1;
+            assert!(rounds < 100);
+            let now = start + Duration::seconds(rounds);
+            for public_id in lookup.next_queries(now) {
+                if silent.contains(&public_id) {
+                    continue;
+                }
+                let mut known = sorted_by_closeness(network.to_vec(), lookup.target());
+                known.truncate(2 * count);
+                assert!(lookup.handle_response(&public_id, known));
+            }
+            lookup.expire_queries(now, Duration::milliseconds(500));
+        }
+    }
+
+    #[test]
+    fn finds_closest_nodes() {
+        let network = (0..50).map(|_| FullId::new().public_id().clone()).collect::<Vec<_>>();
+        let target: XorName = rand::random();
+        let count = 8;
+        let expected = {
+            let mut nodes = sorted_by_closeness(network.clone(), &target);
+            nodes.truncate(count);
+            nodes
+        };
+
+        let mut lookup = Lookup::new(target, count, network[..2].to_vec());
+        run(&mut lookup, &network, count, &[]);
+        assert_eq!(expected, lookup.unverified_result());
+    }
+
+    #[test]
+    fn excludes_silent_nodes() {
+        let network = (0..50).map(|_| FullId::new().public_id().clone()).collect::<Vec<_>>();
+        let target: XorName = rand::random();
+        let count = 8;
+        let sorted = sorted_by_closeness(network.clone(), &target);
+        let silent = vec![sorted[0].clone(), sorted[3].clone()];
+
+        let mut lookup = Lookup::new(target, count, network[..2].to_vec());
+        run(&mut lookup, &network, count, &silent);
+        let expected = sorted.into_iter()
+                             .filter(|public_id| !silent.contains(public_id))
+                             .take(count)
+                             .collect::<Vec<_>>();
+        assert_eq!(expected, lookup.unverified_result());
+    }
+
+    #[test]
+    fn rejects_unexpected_responses() {
+        let network = (0..10).map(|_| FullId::new().public_id().clone()).collect::<Vec<_>>();
+        let target: XorName = rand::random();
+        let mut lookup = Lookup::new(target, 8, network[..1].to_vec());
+        let now = SteadyTime::now();
+
+        // A node we haven't queried.
+        assert!(!lookup.handle_response(&network[1], network.clone()));
+
+        // A node responding under the name of the one we queried, with other keys.
+        let queried = lookup.next_queries(now);
+        assert_eq!(vec![network[0].clone()], queried);
+        let mut impostor = FullId::new().public_id().clone();
+        impostor.set_name(*network[0].name());
+        assert!(!lookup.handle_response(&impostor, network.clone()));
+        assert!(lookup.unverified_result().is_empty());
+        assert!(!lookup.is_complete());
+
+        // A response after the query has expired.
+        lookup.expire_queries(now + Duration::seconds(2), Duration::seconds(1));
+        assert!(!lookup.handle_response(&network[0], network.clone()));
+        assert!(lookup.unverified_result().is_empty());
+        assert!(lookup.is_complete());
+
+        // Nodes added without a query are part of the result.
+        lookup.add_response(network[2].clone(), vec![]);
+        assert_eq!(vec![network[2].clone()], lookup.unverified_result());
+    }
+
+    #[test]
+    fn keeps_real_nodes_reported_under_fake_ids() {
+        let network = (0..20).map(|_| FullId::new().public_id().clone()).collect::<Vec<_>>();
+        let target: XorName = rand::random();
+        let count = 4;
+        let sorted = sorted_by_closeness(network.clone(), &target);
+
+        // The first node we ask reports fake IDs for the real closest nodes' names, before any
+        // real node reports them.
+        let fakes = sorted.iter()
+                          .take(count)
+                          .map(|public_id| {
+                              let mut fake = FullId::new().public_id().clone();
+                              fake.set_name(*public_id.name());
+                              fake
+                          })
+                          .collect::<Vec<_>>();
+        let liar = sorted[count + 5].clone();
+        let mut lookup = Lookup::new(target, count, vec![liar.clone()]);
+        let start = SteadyTime::now();
+        assert_eq!(vec![liar.clone()], lookup.next_queries(start));
+        let mut reported = fakes.clone();
+        reported.push(sorted[count].clone());
+        assert!(lookup.handle_response(&liar, reported));
+
+        // Fake IDs never respond, everyone else reports the real closest nodes.
+        let mut rounds = 0;
+        while !lookup.is_complete() {
+            rounds += 1;
+            assert!(rounds < 100);
+            let now = start + Duration::seconds(rounds);
+            for public_id in lookup.next_queries(now) {
+                if fakes.contains(&public_id) {
+                    continue;
+                }
+                assert!(lookup.handle_response(&public_id, sorted[..count].to_vec()));
+            }
+            lookup.expire_queries(now, Duration::milliseconds(500));
+        }
+        assert_eq!(sorted[..count].to_vec(), lookup.unverified_result());
+    }
+}
@@ -23,8 +23,9 @@ use error::RoutingError;
 use sodiumoxide::crypto::{box_, sign};
 use sodiumoxide::crypto::hash::sha512;
 use authority::Authority;
-use maidsafe_utilities::serialisation::serialise;
-use rustc_serialize::{Decoder, Encoder};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use cbor::CborBytes;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 
 /// Wrapper of all messages.
 ///
@@ -49,8 +50,20 @@ pub enum DirectMessage {
         /// The dynamically calculated quorum size the client's accumulator should use.
         current_quorum_size: usize,
     },
-    /// Sent to the client to indicate that this node is not available as a bootstrap node.
-    BootstrapDeny,
+    /// Sent to the client to indicate that this node is not available as a bootstrap node, or no
+    /// longer is.
+    BootstrapDeny {
+        /// Why the client was refused.
+        reason: BootstrapDenyReason,
+    },
+    /// Sent to a joining node to indicate that it has been put in the queue of nodes waiting for
+    /// this node to become their bootstrap node. Sent again whenever its position changes.
+    BootstrapQueued {
+        /// The joining node's position in the queue, starting at 1.
+        position: usize,
+        /// The estimated time in seconds until the joining node will be accepted.
+        estimated_wait_secs: u64,
+    },
     /// Sent from a newly connected client to the bootstrap node to inform it about the client's
     /// public ID.
     ClientIdentify {
@@ -61,6 +74,22 @@ pub enum DirectMessage {
         /// Indicate whether we intend to remain a client, as opposed to becoming a routing node.
         client_restriction: bool,
     },
+    /// Sent to a node that we haven't heard from for a while, to check that it is still alive.
+    Heartbeat,
+    /// Sent in response to a `Heartbeat`.
+    HeartbeatResponse,
+    /// Sent to the previous hop to acknowledge the receipt of a `HopMessage` that requested it.
+    HopAck {
+        /// The digest of the message's `HopContent`.
+        digest: sha512::Digest,
+    },
+    /// Sent from the proxy node to a client whose message it dropped because the client exceeded
+    /// its rate limit.
+    RateLimitExceeded {
+        /// The time in milliseconds after which the message would have been accepted, or `None`
+        /// if it exceeds the limits on its own.
+        retry_after_ms: Option<u64>,
+    },
     /// Sent from a node to a node, to allow the latter to add the former to its routing table.
     NodeIdentify {
         /// Keys and claimed name, serialised outside routing.
@@ -70,41 +99,80 @@ pub enum DirectMessage {
     },
 }
 
+/// The reason a bootstrap node refuses to act as a client's proxy.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, RustcEncodable, RustcDecodable)]
+pub enum BootstrapDenyReason {
+    /// The bootstrap node's routing table is too small for it to accept clients.
+    RoutingTableTooSmall,
+    /// The bootstrap node doesn't accept any more joining nodes, and its queue is full.
+    JoinQueueFull,
+    /// The bootstrap node is already serving the maximum number of clients.
+    TooManyClients,
+    /// The client was idle and has been disconnected to make room for a new one.
+    Evicted,
+}
+
 /// And individual hop message that represents a part of the route of a message in transit.
 ///
 /// To relay a `SignedMessage` via another node, the `SignedMessage` is wrapped in a `HopMessage`.
 /// The `signature` is from the node that sends this directly to a node in its routing table. To
 /// prevent Man-in-the-middle attacks, the `content` is signed by the original sender.
+///
+/// The `signature` only covers the `content`'s digest, not the serialised message itself, so that
+/// relaying large messages is cheap.
 #[derive(Debug, RustcEncodable, RustcDecodable)]
 pub struct HopMessage {
-    /// Wrapped signed message.
-    content: SignedMessage,
+    /// Wrapped serialised signed message.
+    content: HopContent,
     /// Name of the previous node in the `content`'s route.
     name: XorName,
+    /// Whether the previous node wants the receipt to be acknowledged with a `HopAck`.
+    ack_requested: bool,
+    /// The number of further hops the message may be relayed over. At zero, the receiver handles
+    /// the message if it is for it, but doesn't relay it.
+    hops_remaining: u8,
+    /// The nodes the message has been relayed by so far, or `None` if the route isn't traced.
+    trace: Option<Vec<TracedHop>>,
     /// Signature to be validated against `name`'s public key.
     signature: sign::Signature,
 }
 
 impl HopMessage {
     /// Wrap `content` for transmission to the next hop and sign it.
-    pub fn new(content: SignedMessage,
+    pub fn new(content: HopContent,
                name: XorName,
+               ack_requested: bool,
+               hops_remaining: u8,
+               trace: Option<Vec<TracedHop>>,
                sign_key: &sign::SecretKey)
                -> Result<HopMessage, RoutingError> {
-        let bytes_to_sign = try!(serialise(&(&content, &name)));
+        let bytes_to_sign = try!(serialise(&(content.digest(),
+                                             &name,
+                                             ack_requested,
+                                             hops_remaining,
+                                             &trace)));
         Ok(HopMessage {
             content: content,
             name: name,
+            ack_requested: ack_requested,
+            hops_remaining: hops_remaining,
+            trace: trace,
             signature: sign::sign_detached(&bytes_to_sign, sign_key),
         })
     }
 
-    /// Validate that the message is signed by `verification_key` contained in message.
+    /// Validate that the message is signed by `verification_key` contained in message. Since the
+    /// content's digest is computed on receipt, this also confirms that the content is what the
+    /// previous node sent.
     ///
     /// This does not imply that the message came from a known node. That requires a check against
     /// the routing table to identify the name associated with the `verification_key`.
     pub fn verify(&self, verification_key: &sign::PublicKey) -> Result<(), RoutingError> {
-        let signed_bytes = try!(serialise(&(&self.content, &self.name)));
+        let signed_bytes = try!(serialise(&(self.content.digest(),
+                                            &self.name,
+                                            self.ack_requested,
+                                            self.hops_remaining,
+                                            &self.trace)));
         if sign::verify_detached(&self.signature, &signed_bytes, verification_key) {
             Ok(())
         } else {
@@ -112,14 +180,30 @@ impl HopMessage {
         }
     }
 
-    /// Returns the `SignedMessage` and the `name` of the previous routing node.
+    /// Returns the serialised `SignedMessage`.
     ///
     /// Does not validate the message! [#verify] must be called to ensure that the sender is valid
-    /// and signed the message.
-    pub fn content(&self) -> &SignedMessage {
+    /// and signed the message, and `HopContent::signed_message` to ensure that the originator
+    /// signed the content.
+    pub fn content(&self) -> &HopContent {
         &self.content
     }
 
+    /// The number of further hops the message may be relayed over.
+    pub fn hops_remaining(&self) -> u8 {
+        self.hops_remaining
+    }
+
+    /// Whether the previous node wants the receipt to be acknowledged.
+    pub fn ack_requested(&self) -> bool {
+        self.ack_requested
+    }
+
+    /// The nodes the message has been relayed by so far, if the route is traced.
+    pub fn trace(&self) -> Option<&Vec<TracedHop>> {
+        self.trace.as_ref()
+    }
+
     /// The name of the previous node in the signed message's route.
     pub fn name(&self) -> &XorName {
         &self.name
@@ -127,7 +211,7 @@ impl HopMessage {
 }
 
 /// Wrapper around a routing message, signed by the originator of the message.
-#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Debug, RustcEncodable, RustcDecodable)]
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Debug)]
 pub struct SignedMessage {
     /// A request or response type message.
     content: RoutingMessage,
@@ -136,23 +220,42 @@ pub struct SignedMessage {
     /// For clients this is easily verifiable since their name is computed from the ID. For nodes it
     /// needs to be confirmed by their `NodeManager`.
     public_id: PublicId,
+    /// Whether the nodes relaying the message record its route.
+    trace_route: bool,
     signature: sign::Signature,
+    /// The hash of the serialised `content`, which is the same for all the members of a group
+    /// sending the message. It is not encoded, but computed on decoding.
+    content_digest: sha512::Digest,
 }
 
 impl SignedMessage {
     /// Creates a `SignedMessage` with the given `content` and signed by the given `full_id`.
     pub fn new(content: RoutingMessage, full_id: &FullId) -> Result<SignedMessage, RoutingError> {
-        let bytes_to_sign = try!(serialise(&(&content, full_id.public_id())));
+        SignedMessage::with_trace(content, full_id, false)
+    }
+
+    /// Creates a `SignedMessage` like `new`, whose route is recorded by the nodes relaying it if
+    /// `trace_route` is `true`.
+    pub fn with_trace(content: RoutingMessage,
+                      full_id: &FullId,
+                      trace_route: bool)
+                      -> Result<SignedMessage, RoutingError> {
+        let content_bytes = try!(serialise(&content));
+        let bytes_to_sign = try!(signed_bytes(&content_bytes, full_id.public_id(), trace_route));
         Ok(SignedMessage {
             content: content,
             public_id: full_id.public_id().clone(),
+            trace_route: trace_route,
             signature: sign::sign_detached(&bytes_to_sign, full_id.signing_private_key()),
+            content_digest: sha512::hash(&content_bytes),
         })
     }
 
     /// Confirms the signature against the claimed public ID.
     pub fn check_integrity(&self) -> Result<(), RoutingError> {
-        let signed_bytes = try!(serialise(&(&self.content, &self.public_id)));
+        let signed_bytes = try!(signed_bytes(&try!(serialise(&self.content)),
+                                             &self.public_id,
+                                             self.trace_route));
         if sign::verify_detached(&self.signature,
                                  &signed_bytes,
                                  self.public_id().signing_public_key()) {
@@ -171,6 +274,214 @@ impl SignedMessage {
     pub fn public_id(&self) -> &PublicId {
         &self.public_id
     }
+
+    /// Whether the originator wants the message's route to be recorded.
+    pub fn trace_route(&self) -> bool {
+        self.trace_route
+    }
+
+    /// The signature of the `content`, `public_id` and `trace_route` by the originator of the
+    /// message.
+    pub fn signature(&self) -> &sign::Signature {
+        &self.signature
+    }
+
+    /// The SHA-512 hash of the serialised `content`. Unlike the `HopContent` digest, this doesn't
+    /// depend on the sender, so it identifies a group message sent by several group members.
+    pub fn content_digest(&self) -> &sha512::Digest {
+        &self.content_digest
+    }
+}
+
+impl Encodable for SignedMessage {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        (&self.content, &self.public_id, self.trace_route, &self.signature).encode(encoder)
+    }
+}
+
+impl Decodable for SignedMessage {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<SignedMessage, D::Error> {
+        let (content, public_id, trace_route, signature): (RoutingMessage,
+                                                           PublicId,
+                                                           bool,
+                                                           sign::Signature) =
+            try!(Decodable::decode(decoder));
+        let content_bytes = match serialise(&content) {
+            Ok(content_bytes) => content_bytes,
+            Err(error) => return Err(decoder.error(&format!("{:?}", error))),
+        };
+        Ok(SignedMessage {
+            content: content,
+            public_id: public_id,
+            trace_route: trace_route,
+            signature: signature,
+            content_digest: sha512::hash(&content_bytes),
+        })
+    }
+}
+
+// The bytes the originator of a message signs: the serialised routing message, nested so that
+// the receiver can hash it without serialising it again, the originator's ID and the trace flag.
+fn signed_bytes(content_bytes: &[u8],
+                public_id: &PublicId,
+                trace_route: bool)
+                -> Result<Vec<u8>, RoutingError> {
+    Ok(try!(serialise(&(CborBytes(content_bytes.to_vec()), public_id, trace_route))))
+}
+
+/// A serialised `SignedMessage` and its originator's signature.
+///
+/// The originator signs the serialised bytes themselves, so checking the message's integrity is a
+/// single signature verification over the received buffer. Relaying nodes pass it on as an opaque
+/// buffer, and identify it by its digest, which each node computes on receipt rather than
+/// receiving it.
+#[derive(Clone, Debug)]
+pub struct HopContent {
+    // The serialised content, public ID and trace flag of the `SignedMessage`, with the content
+    // itself nested as serialised bytes
+    bytes: CborBytes,
+    signature: sign::Signature,
+    digest: sha512::Digest,
+}
+
+impl HopContent {
+    /// Serialises `signed_msg` for transmission.
+    pub fn new(signed_msg: &SignedMessage) -> Result<HopContent, RoutingError> {
+        let bytes = try!(signed_bytes(&try!(serialise(signed_msg.content())),
+                                      signed_msg.public_id(),
+                                      signed_msg.trace_route()));
+        Ok(HopContent::from_parts(bytes, *signed_msg.signature()))
+    }
+
+    fn from_parts(bytes: Vec<u8>, signature: sign::Signature) -> HopContent {
+        HopContent {
+            digest: sha512::hash(&bytes),
+            bytes: CborBytes(bytes),
+            signature: signature,
+        }
+    }
+
+    /// Deserialises the `SignedMessage` and confirms that the serialised bytes are signed by its
+    /// originator.
+    pub fn signed_message(&self) -> Result<SignedMessage, RoutingError> {
+        let (content_bytes, public_id, trace_route): (CborBytes, PublicId, bool) =
+            try!(deserialise(&self.bytes.0));
+        if !sign::verify_detached(&self.signature,
+                                  &self.bytes.0,
+                                  public_id.signing_public_key()) {
+            return Err(RoutingError::FailedSignature);
+        }
+        Ok(SignedMessage {
+            content: try!(deserialise(&content_bytes.0)),
+            public_id: public_id,
+            trace_route: trace_route,
+            signature: self.signature,
+            content_digest: sha512::hash(&content_bytes.0),
+        })
+    }
+
+    /// The SHA-512 hash of the serialised message.
+    pub fn digest(&self) -> &sha512::Digest {
+        &self.digest
+    }
+}
+
+impl Encodable for HopContent {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        (&self.bytes, &self.signature).encode(encoder)
+    }
+}
+
+impl Decodable for HopContent {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<HopContent, D::Error> {
+        let (bytes, signature): (CborBytes, sign::Signature) = try!(Decodable::decode(decoder));
+        Ok(HopContent::from_parts(bytes.0, signature))
+    }
+}
+
+/// A node on the route of a traced message.
+///
+/// The node signs the originator's signature of the message and the previous hop's signature,
+/// which proves that the message passed through it after the previous hop. Hops can therefore not
+/// be removed from the middle of a route without the node that removed them signing the result.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub struct TracedHop {
+    public_id: PublicId,
+    signature: sign::Signature,
+}
+
+impl TracedHop {
+    /// Records that `signed_msg` has passed through the node with the given `full_id`, after the
+    /// `previous` hop, if any.
+    pub fn new(signed_msg: &SignedMessage,
+               previous: Option<&TracedHop>,
+               full_id: &FullId)
+               -> Result<TracedHop, RoutingError> {
+        let bytes_to_sign = try!(serialise(&(signed_msg.signature(),
+                                             previous.map(|hop| &hop.signature),
+                                             full_id.public_id())));
+        Ok(TracedHop {
+            public_id: full_id.public_id().clone(),
+            signature: sign::sign_detached(&bytes_to_sign, full_id.signing_private_key()),
+        })
+    }
+
+    /// Confirms that the node signed the message with the given originator's signature, after the
+    /// `previous` hop.
+    pub fn verify(&self,
+                  message_signature: &sign::Signature,
+                  previous: Option<&TracedHop>)
+                  -> Result<(), RoutingError> {
+        let signed_bytes = try!(serialise(&(message_signature,
+                                            previous.map(|hop| &hop.signature),
+                                            &self.public_id)));
+        if sign::verify_detached(&self.signature,
+                                 &signed_bytes,
+                                 self.public_id.signing_public_key()) {
+            Ok(())
+        } else {
+            Err(RoutingError::FailedSignature)
+        }
+    }
+
+    /// Confirms that each hop of the `route` signed the message with the given originator's
+    /// signature after its predecessor.
+    pub fn verify_route(message_signature: &sign::Signature,
+                        route: &[TracedHop])
+                        -> Result<(), RoutingError> {
+        let mut previous = None;
+        for hop in route {
+            try!(hop.verify(message_signature, previous));
+            previous = Some(hop);
+        }
+        Ok(())
+    }
+
+    /// The `PublicId` of the node.
+    pub fn public_id(&self) -> &PublicId {
+        &self.public_id
+    }
+}
+
+/// The signatures of the members of a group authority which sent the same message.
+///
+/// This is accumulated from the individual `SignedMessage`s until the quorum is reached, and
+/// allows the recipient to verify the message without trusting the nodes that relayed it.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct GroupProof {
+    signatures: Vec<(PublicId, sign::Signature)>,
+}
+
+impl GroupProof {
+    /// Creates a `GroupProof` from the group members' `PublicId`s and signatures.
+    pub fn new(signatures: Vec<(PublicId, sign::Signature)>) -> GroupProof {
+        GroupProof { signatures: signatures }
+    }
+
+    /// The `PublicId` and signature of each group member that signed the message.
+    pub fn signatures(&self) -> &[(PublicId, sign::Signature)] {
+        &self.signatures
+    }
 }
 
 /// Variant type to hold `either` a request or response.
@@ -198,6 +509,56 @@ impl RoutingMessage {
             RoutingMessage::Response(ref msg) => &msg.dst,
         }
     }
+
+    /// Returns the data carried by the message, if any. This includes the data in a request that
+    /// is returned as part of a failure response.
+    pub fn data(&self) -> Option<&Data> {
+        match *self {
+            RoutingMessage::Request(ref msg) => msg.data(),
+            RoutingMessage::Response(ref msg) => {
+                match msg.content {
+                    ResponseContent::GetSuccess(ref data, _) => Some(data),
+                    ResponseContent::GetFailure { ref request, .. } |
+                    ResponseContent::PutFailure { ref request, .. } |
+                    ResponseContent::PostFailure { ref request, .. } |
+                    ResponseContent::DeleteFailure { ref request, .. } => request.data(),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Returns the ID of the message, if it has one.
+    pub fn message_id(&self) -> Option<&MessageId> {
+        match *self {
+            RoutingMessage::Request(ref msg) => {
+                match msg.content {
+                    RequestContent::FindNode { ref id, .. } |
+                    RequestContent::GetGroupIds { ref id } |
+                    RequestContent::Get(_, ref id) |
+                    RequestContent::Put(_, ref id) |
+                    RequestContent::Post(_, ref id) |
+                    RequestContent::Delete(_, ref id) => Some(id),
+                    _ => None,
+                }
+            }
+            RoutingMessage::Response(ref msg) => {
+                match msg.content {
+                    ResponseContent::FindNode { ref id, .. } |
+                    ResponseContent::GetGroupIds { ref id, .. } |
+                    ResponseContent::GetSuccess(_, ref id) |
+                    ResponseContent::PutSuccess(_, ref id) |
+                    ResponseContent::PostSuccess(_, ref id) |
+                    ResponseContent::DeleteSuccess(_, ref id) |
+                    ResponseContent::GetFailure { ref id, .. } |
+                    ResponseContent::PutFailure { ref id, .. } |
+                    ResponseContent::PostFailure { ref id, .. } |
+                    ResponseContent::DeleteFailure { ref id, .. } => Some(id),
+                    _ => None,
+                }
+            }
+        }
+    }
 }
 
 /// A request message wrapper
@@ -211,6 +572,18 @@ pub struct RequestMessage {
     pub content: RequestContent,
 }
 
+impl RequestMessage {
+    /// Returns the data carried by the request, if any.
+    pub fn data(&self) -> Option<&Data> {
+        match self.content {
+            RequestContent::Put(ref data, _) |
+            RequestContent::Post(ref data, _) |
+            RequestContent::Delete(ref data, _) => Some(data),
+            _ => None,
+        }
+    }
+}
+
 /// A response message wrapper
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Debug, RustcEncodable, RustcDecodable)]
 pub struct ResponseMessage {
@@ -222,6 +595,64 @@ pub struct ResponseMessage {
     pub content: ResponseContent,
 }
 
+impl ResponseMessage {
+    /// Verifies that `proof` contains valid signatures of this response by at least
+    /// `quorum_size` distinct nodes.
+    ///
+    /// This confirms that the response was signed by `quorum_size` distinct keys. It does not
+    /// confirm that the signing nodes actually are members of the source group authority: a
+    /// lying proxy node can generate any number of keys itself. Use `verify_group_members` to
+    /// check the signers against the group's `PublicId`s, if they are known.
+    pub fn verify_group_proof(&self,
+                              proof: &GroupProof,
+                              quorum_size: usize)
+                              -> Result<(), RoutingError> {
+        if !self.src.is_group() {
+            return Err(RoutingError::BadAuthority);
+        }
+
+        let content_bytes = try!(serialise(&RoutingMessage::Response(self.clone())));
+        let mut signers: Vec<&PublicId> = Vec::with_capacity(proof.signatures().len());
+        for &(ref public_id, ref signature) in proof.signatures() {
+            if signers.iter().any(|signer| {
+                signer.signing_public_key() == public_id.signing_public_key() ||
+                signer.name() == public_id.name()
+            }) {
+                return Err(RoutingError::DuplicateSignatures);
+            }
+            // Messages from groups are never traced.
+            let signed_bytes = try!(signed_bytes(&content_bytes, public_id, false));
+            if !sign::verify_detached(signature, &signed_bytes, public_id.signing_public_key()) {
+                return Err(RoutingError::FailedSignature);
+            }
+            signers.push(public_id);
+        }
+
+        if signers.len() < quorum_size {
+            return Err(RoutingError::NotEnoughSignatures);
+        }
+        Ok(())
+    }
+
+    /// Verifies that all the signers in `proof` are among the `PublicId`s in `group`, comparing
+    /// their keys as well as their names, since anyone can create an ID with a member's name.
+    ///
+    /// A node can take the group's `PublicId`s from its `Node::routing_table`, if the group is
+    /// close to it. A client can request them from the group with
+    /// `Client::send_get_group_ids_request`. Together with `verify_group_proof`, this confirms
+    /// that the response was sent by the group, as far as `group` is correct.
+    pub fn verify_group_members(&self,
+                                proof: &GroupProof,
+                                group: &[PublicId])
+                                -> Result<(), RoutingError> {
+        if proof.signatures().iter().all(|&(ref public_id, _)| group.contains(public_id)) {
+            Ok(())
+        } else {
+            Err(RoutingError::BadAuthority)
+        }
+    }
+}
+
 /// The request types
 #[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Debug, RustcEncodable, RustcDecodable)]
 pub enum RequestContent {
@@ -236,14 +667,29 @@ pub enum RequestContent {
     },
     /// Notify a joining node's `NodeManager` so that it expects a `GetCloseGroup` request from it.
     ExpectCloseNode {
-        /// The joining node's `PublicId` (public keys and name)
+        /// The joining node's `PublicId` (public keys and relocated name)
         expect_id: PublicId,
+        /// The joining node's name before relocation
+        original_name: XorName,
+        /// The names of the nodes the relocated name was computed from
+        close_nodes: Vec<XorName>,
     },
     /// Request the `PublicId`s of the recipient's close group.
     ///
     /// This is sent from a joining node to its `NodeManager` to request the `PublicId`s of the
     /// `NodeManager`'s members.
     GetCloseGroup,
+    /// Provide the resource proof demanded by a `ResourceProofChallenge`.
+    ///
+    /// This is sent from a joining node to each member of its `NodeManager` that challenged it.
+    ResourceProof {
+        /// The seed of the challenge
+        seed: Vec<u8>,
+        /// The data derived from the seed
+        data: Vec<u8>,
+        /// The nonce that completes the proof
+        nonce: u64,
+    },
     /// Request a direct connection to the recipient.
     Connect,
     /// Send our endpoints encrypted to a node we wish to connect to and have the keys for.
@@ -262,6 +708,23 @@ pub enum RequestContent {
         /// Nonce used to provide a salt in the encrytped message.
         nonce_bytes: [u8; box_::NONCEBYTES],
     },
+    /// Ask a node for the nodes it knows that are closest to `target`.
+    ///
+    /// This is sent from a `ManagedNode` to another `ManagedNode` as part of an iterative lookup.
+    FindNode {
+        /// The name to find the closest nodes to
+        target: XorName,
+        /// The ID of the lookup
+        id: MessageId,
+    },
+    /// Ask each member of a group for the `PublicId`s of the group's members.
+    ///
+    /// This is sent from a `Client` to a group authority, to check the signers of the group's
+    /// responses against them.
+    GetGroupIds {
+        /// The ID of the request
+        id: MessageId,
+    },
     /// Message from upper layers sending network state on any network churn event.
     Refresh(Vec<u8>),
     // ---------- External ------------
@@ -288,6 +751,8 @@ pub enum ResponseContent {
     GetNetworkName {
         /// Supplied `PublicId`, but with the new name
         relocated_id: PublicId,
+        /// The names of the nodes the new name was computed from
+        close_nodes: Vec<XorName>,
     },
     /// Reply with the requested `PublicId`.
     ///
@@ -314,6 +779,51 @@ pub enum ResponseContent {
         /// Our close group `PublicId`s.
         close_group_ids: Vec<PublicId>,
     },
+    /// Reply with the `PublicId`s of the sender and the nodes it knows closest to `target`.
+    ///
+    /// Sent from a `ManagedNode` to the `ManagedNode` that sent the `FindNode` request.
+    FindNode {
+        /// The name the nodes are closest to
+        target: XorName,
+        /// The ID of the lookup
+        id: MessageId,
+        /// The closest nodes' `PublicId`s
+        close_nodes: Vec<PublicId>,
+    },
+    /// Reply with the `PublicId`s of the group's members.
+    ///
+    /// Sent from each member of a group authority to the `Client` that sent `GetGroupIds`.
+    GetGroupIds {
+        /// The ID of the request
+        id: MessageId,
+        /// The group members' `PublicId`s
+        group_ids: Vec<PublicId>,
+    },
+    /// Demand a resource proof before answering a `GetCloseGroup` request.
+    ///
+    /// Sent from each member of a `NodeManager` individually to the joining node, which needs to
+    /// send a matching `ResourceProof` to that member's `ManagedNode` authority.
+    ResourceProofChallenge {
+        /// Random seed the proof needs to be computed from
+        seed: Vec<u8>,
+        /// The number of leading zero bits the proof's hash needs to have
+        difficulty: u8,
+        /// The number of bytes of data the proof needs to contain
+        data_size: usize,
+    },
+    /// Return the route a traced message has taken.
+    ///
+    /// Sent from the `ManagedNode` that received the traced message to its source authority.
+    RouteTrace {
+        /// The originator's signature of the traced message
+        message_signature: sign::Signature,
+        /// The ID of the traced message, if it has one
+        id: Option<MessageId>,
+        /// The destination authority of the traced message
+        dst: Authority,
+        /// The nodes the message passed through, including the originator and the recipient
+        route: Vec<TracedHop>,
+    },
     // ---------- External ------------
     /// Reply with the requested data (may not be ignored)
     ///
@@ -363,3 +873,258 @@ pub enum ResponseContent {
         external_error_indicator: Vec<u8>,
     },
 }
+
+#[cfg(test)]
+mod test {
+    extern crate rand;
+
+    use authority::Authority;
+    use error::RoutingError;
+    use id::FullId;
+    use data::{Data, DataRequest};
+    use itertools::Itertools;
+    use maidsafe_utilities::serialisation::{deserialise, serialise};
+    use plain_data::PlainData;
+    use super::{GroupProof, HopContent, HopMessage, RequestContent, RequestMessage,
+                ResponseContent, ResponseMessage, RoutingMessage, SignedMessage, TracedHop};
+    use types::MessageId;
+
+    fn sign_response(response: &ResponseMessage, full_id: &FullId) -> SignedMessage {
+        unwrap_result!(SignedMessage::new(RoutingMessage::Response(response.clone()), full_id))
+    }
+
+    #[test]
+    fn verify_group_proof() {
+        let response = ResponseMessage {
+            src: Authority::NaeManager(rand::random()),
+            dst: Authority::ManagedNode(rand::random()),
+            content: ResponseContent::PutSuccess(::sodiumoxide::crypto::hash::sha512::hash(&[]),
+                                                 MessageId::new()),
+        };
+        let full_ids = (0..3).map(|_| FullId::new()).collect::<Vec<_>>();
+        let signatures = full_ids.iter()
+                                 .map(|full_id| {
+                                     let signed_msg = sign_response(&response, full_id);
+                                     (signed_msg.public_id().clone(),
+                                      signed_msg.signature().clone())
+                                 })
+                                 .collect::<Vec<_>>();
+
+        // valid proof
+        let proof = GroupProof::new(signatures.clone());
+        assert!(response.verify_group_proof(&proof, 3).is_ok());
+
+        // not enough signatures
+        match response.verify_group_proof(&proof, 4) {
+            Err(RoutingError::NotEnoughSignatures) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // duplicate signer
+        let mut duplicated = signatures.clone();
+        duplicated.push(signatures[0].clone());
+        match response.verify_group_proof(&GroupProof::new(duplicated), 3) {
+            Err(RoutingError::DuplicateSignatures) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // signature of a different response
+        let mut other_response = response.clone();
+        other_response.src = Authority::NaeManager(rand::random());
+        match other_response.verify_group_proof(&proof, 3) {
+            Err(RoutingError::FailedSignature) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // non-group source
+        let mut individual_response = response.clone();
+        individual_response.src = Authority::ManagedNode(rand::random());
+        match individual_response.verify_group_proof(&proof, 3) {
+            Err(RoutingError::BadAuthority) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // signers in and not in the group
+        let mut group = full_ids.iter().map(|full_id| full_id.public_id().clone()).collect_vec();
+        assert!(response.verify_group_members(&proof, &group).is_ok());
+        let _ = group.pop();
+        match response.verify_group_members(&proof, &group) {
+            Err(RoutingError::BadAuthority) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // signer with a member's name, but its own keys
+        let group = full_ids.iter().map(|full_id| full_id.public_id().clone()).collect_vec();
+        let mut impostor_id = FullId::new();
+        impostor_id.public_id_mut().set_name(*full_ids[0].public_id().name());
+        let mut impostor_signatures = signatures[1..].to_vec();
+        let signed_msg = sign_response(&response, &impostor_id);
+        impostor_signatures.push((signed_msg.public_id().clone(), signed_msg.signature().clone()));
+        let impostor_proof = GroupProof::new(impostor_signatures);
+        assert!(response.verify_group_proof(&impostor_proof, 3).is_ok());
+        match response.verify_group_members(&impostor_proof, &group) {
+            Err(RoutingError::BadAuthority) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn data() {
+        let data = Data::PlainData(PlainData::new(rand::random(), vec![1, 2, 3]));
+        let put = RequestMessage {
+            src: Authority::ManagedNode(rand::random()),
+            dst: Authority::NaeManager(data.name()),
+            content: RequestContent::Put(data.clone(), MessageId::new()),
+        };
+        assert_eq!(Some(&data), RoutingMessage::Request(put.clone()).data());
+
+        let get = RequestMessage {
+            src: Authority::ManagedNode(rand::random()),
+            dst: Authority::NaeManager(data.name()),
+            content: RequestContent::Get(DataRequest::PlainData(data.name()), MessageId::new()),
+        };
+        assert_eq!(None, RoutingMessage::Request(get).data());
+
+        let get_success = ResponseMessage {
+            src: Authority::NaeManager(data.name()),
+            dst: Authority::ManagedNode(rand::random()),
+            content: ResponseContent::GetSuccess(data.clone(), MessageId::new()),
+        };
+        assert_eq!(Some(&data), RoutingMessage::Response(get_success).data());
+
+        let put_failure = ResponseMessage {
+            src: Authority::NaeManager(data.name()),
+            dst: Authority::ManagedNode(rand::random()),
+            content: ResponseContent::PutFailure {
+                id: MessageId::new(),
+                request: put,
+                external_error_indicator: vec![],
+            },
+        };
+        assert_eq!(Some(&data), RoutingMessage::Response(put_failure).data());
+    }
+
+    #[test]
+    fn traced_hop() {
+        let request = RequestMessage {
+            src: Authority::ManagedNode(rand::random()),
+            dst: Authority::NaeManager(rand::random()),
+            content: RequestContent::GetCloseGroup,
+        };
+        let signed_msg = unwrap_result!(SignedMessage::new(RoutingMessage::Request(request),
+                                                           &FullId::new()));
+        let other_msg = unwrap_result!(SignedMessage::new(signed_msg.content().clone(),
+                                                          &FullId::new()));
+        let first = unwrap_result!(TracedHop::new(&signed_msg, None, &FullId::new()));
+        let second = unwrap_result!(TracedHop::new(&signed_msg, Some(&first), &FullId::new()));
+        let third = unwrap_result!(TracedHop::new(&signed_msg, Some(&second), &FullId::new()));
+
+        assert!(first.verify(signed_msg.signature(), None).is_ok());
+        assert!(TracedHop::verify_route(signed_msg.signature(),
+                                        &[first.clone(), second.clone(), third.clone()])
+                    .is_ok());
+        match first.verify(other_msg.signature(), None) {
+            Err(RoutingError::FailedSignature) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        // Hops can't be removed from the route.
+        match TracedHop::verify_route(signed_msg.signature(), &[first, third]) {
+            Err(RoutingError::FailedSignature) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn trace_route_is_signed() {
+        let request = RequestMessage {
+            src: Authority::ManagedNode(rand::random()),
+            dst: Authority::NaeManager(rand::random()),
+            content: RequestContent::GetCloseGroup,
+        };
+        let full_id = FullId::new();
+        let signed_msg = unwrap_result!(SignedMessage::with_trace(RoutingMessage::Request(request),
+                                                                  &full_id,
+                                                                  true));
+        assert!(signed_msg.trace_route());
+        assert!(signed_msg.check_integrity().is_ok());
+
+        let mut stripped = signed_msg.clone();
+        stripped.trace_route = false;
+        match stripped.check_integrity() {
+            Err(RoutingError::FailedSignature) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn content_digest_is_computed_on_decode() {
+        let request = RequestMessage {
+            src: Authority::ManagedNode(rand::random()),
+            dst: Authority::NaeManager(rand::random()),
+            content: RequestContent::GetCloseGroup,
+        };
+        let signed_msg = unwrap_result!(SignedMessage::new(RoutingMessage::Request(request),
+                                                           &FullId::new()));
+        let bytes = unwrap_result!(serialise(&signed_msg));
+        let received: SignedMessage = unwrap_result!(deserialise(&bytes));
+        assert_eq!(signed_msg, received);
+
+        // A digest set by the sender is not encoded, so the receiver computes the real one.
+        let mut forged = signed_msg.clone();
+        forged.content_digest = ::sodiumoxide::crypto::hash::sha512::hash(&[]);
+        let bytes = unwrap_result!(serialise(&forged));
+        let received: SignedMessage = unwrap_result!(deserialise(&bytes));
+        assert_eq!(signed_msg.content_digest(), received.content_digest());
+    }
+
+    #[test]
+    fn hop_message() {
+        let data = Data::PlainData(PlainData::new(rand::random(), vec![7; 1024]));
+        let request = RequestMessage {
+            src: Authority::ManagedNode(rand::random()),
+            dst: Authority::NaeManager(data.name()),
+            content: RequestContent::Put(data, MessageId::new()),
+        };
+        let signed_msg = unwrap_result!(SignedMessage::new(RoutingMessage::Request(request),
+                                                           &FullId::new()));
+        let content = unwrap_result!(HopContent::new(&signed_msg));
+        let hop_id = FullId::new();
+        let hop_msg = unwrap_result!(HopMessage::new(content.clone(),
+                                                     rand::random(),
+                                                     false,
+                                                     1,
+                                                     None,
+                                                     hop_id.signing_private_key()));
+
+        assert!(hop_msg.verify(hop_id.public_id().signing_public_key()).is_ok());
+        assert_eq!(signed_msg, unwrap_result!(hop_msg.content().signed_message()));
+        match hop_msg.verify(FullId::new().public_id().signing_public_key()) {
+            Err(RoutingError::FailedSignature) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // The digest is computed on receipt.
+        let bytes = unwrap_result!(serialise(&hop_msg));
+        let received: HopMessage = unwrap_result!(deserialise(&bytes));
+        assert_eq!(content.digest(), received.content().digest());
+        assert!(received.verify(hop_id.public_id().signing_public_key()).is_ok());
+
+        // Content that doesn't match the originator's signature
+        let other_msg = unwrap_result!(SignedMessage::new(signed_msg.content().clone(),
+                                                          &FullId::new()));
+        assert_eq!(signed_msg.content_digest(), other_msg.content_digest());
+        let other_content = unwrap_result!(HopContent::new(&other_msg));
+        let tampered = HopContent::from_parts(other_content.bytes.0, content.signature);
+        let hop_msg = unwrap_result!(HopMessage::new(tampered,
+                                                     rand::random(),
+                                                     false,
+                                                     1,
+                                                     None,
+                                                     hop_id.signing_private_key()));
+        assert!(hop_msg.verify(hop_id.public_id().signing_public_key()).is_ok());
+        match hop_msg.content().signed_message() {
+            Err(RoutingError::FailedSignature) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+}
@@ -20,8 +20,10 @@ use std::sync::mpsc::{Receiver, Sender, channel};
 
 use action::Action;
 use authority::Authority;
+use config::Config;
 use core::Core;
 use data::{Data, DataRequest};
+use diagnostics::RoutingTableSnapshot;
 use error::{InterfaceError, RoutingError};
 use event::Event;
 use messages::{RequestContent, RequestMessage, ResponseContent, ResponseMessage, RoutingMessage};
@@ -54,10 +56,17 @@ impl Node {
     ///
     /// The intial `Node` object will have newly generated keys.
     pub fn new(event_sender: Sender<Event>) -> Result<Node, RoutingError> {
+        Node::with_config(event_sender, Config::default())
+    }
+
+    /// Create a new `Node` with the given configuration.
+    ///
+    /// Apart from that, this behaves like `Node::new`.
+    pub fn with_config(event_sender: Sender<Event>, config: Config) -> Result<Node, RoutingError> {
         sodiumoxide::init();  // enable shared global (i.e. safe to multithread now)
 
         // start the handler for routing without a restriction to become a full node
-        let (action_sender, raii_joiner) = try!(Core::new(event_sender, false, None));
+        let (action_sender, raii_joiner) = try!(Core::new(event_sender, false, None, config));
 
         let (tx, rx) = channel();
         Ok(Node {
@@ -299,9 +308,88 @@ impl Node {
         Ok(try!(result_rx.recv()))
     }
 
+    /// Returns the names of the `count` nodes closest to `name`, including this one, out of the
+    /// nodes this node knows about.
+    ///
+    /// This is only guaranteed to be the actual `count` closest nodes in the network if `name` is
+    /// close to this node's own name.
+    pub fn closest_known_nodes(&self,
+                               name: &XorName,
+                               count: usize)
+                               -> Result<Vec<XorName>, InterfaceError> {
+        let (result_tx, result_rx) = channel();
+        try!(self.action_sender.send(Action::ClosestNodes {
+            name: *name,
+            count: count,
+            result_tx: result_tx,
+        }));
+        Ok(try!(result_rx.recv()))
+    }
+
+    /// Returns whether this node is a member of the group authority with address `name`, i. e.
+    /// whether it is among the `group_size` nodes closest to `name`, according to its routing
+    /// table.
+    pub fn is_responsible_for(&self, name: &XorName) -> Result<bool, InterfaceError> {
+        let (result_tx, result_rx) = channel();
+        try!(self.action_sender.send(Action::IsResponsibleFor {
+            name: *name,
+            result_tx: result_tx,
+        }));
+        Ok(try!(result_rx.recv()))
+    }
+
+    /// Start a network-wide lookup for the group of nodes closest to `target`.
+    ///
+    /// The lookup iteratively queries the closest nodes known so far for the nodes they know. Its
+    /// result is raised as an `Event::LookupComplete` with the given `id`. The nodes it finds are
+    /// known to hold the keys in their `PublicId`s, but their names are taken from the other
+    /// nodes' reports and are not verified.
+    pub fn find_nodes(&self, target: XorName, id: MessageId) -> Result<(), InterfaceError> {
+        try!(self.action_sender.send(Action::FindNodes {
+            target: target,
+            id: id,
+            result_tx: self.interface_result_tx.clone(),
+        }));
+
+        try!(self.interface_result_rx.recv())
+    }
+
+    /// Returns an estimate of the number of nodes in the network.
+    ///
+    /// It is computed from the distances between this node and its close group, and updated
+    /// whenever the routing table changes.
+    pub fn network_size(&self) -> Result<u64, InterfaceError> {
+        let (result_tx, result_rx) = channel();
+        try!(self.action_sender.send(Action::NetworkSize { result_tx: result_tx }));
+        Ok(try!(result_rx.recv()))
+    }
+
+    /// Returns a snapshot of this node's routing table and connections.
+    pub fn routing_table(&self) -> Result<RoutingTableSnapshot, InterfaceError> {
+        let (result_tx, result_rx) = channel();
+        try!(self.action_sender.send(Action::RoutingTable { result_tx: result_tx }));
+        Ok(try!(result_rx.recv()))
+    }
+
+    /// Send the message and have the nodes relaying it record its route, for debugging.
+    ///
+    /// The member of the destination authority closest to its name returns the route, which is
+    /// raised as an `Event::RouteTraced`. Messages from a group authority are sent untraced.
+    pub fn send_traced(&self, routing_msg: RoutingMessage) -> Result<(), InterfaceError> {
+        self.send_routing_message(routing_msg, true)
+    }
+
     fn send_action(&self, routing_msg: RoutingMessage) -> Result<(), InterfaceError> {
+        self.send_routing_message(routing_msg, false)
+    }
+
+    fn send_routing_message(&self,
+                            routing_msg: RoutingMessage,
+                            trace_route: bool)
+                            -> Result<(), InterfaceError> {
         try!(self.action_sender.send(Action::NodeSendMessage {
             content: routing_msg,
+            trace_route: trace_route,
             result_tx: self.interface_result_tx.clone(),
         }));
 
@@ -0,0 +1,166 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use time::{Duration, SteadyTime};
+
+/// A token bucket: tokens are added at a constant rate, up to a maximum, and each unit of usage
+/// takes a token out.
+pub struct TokenBucket {
+    rate_per_sec: u64,
+    capacity: u64,
+    // Tokens are counted in millionths, so that they can be added in small time increments.
+    micro_tokens: u64,
+    last_update: SteadyTime,
+}
+
+impl TokenBucket {
+    /// Create a full bucket that fills up at `rate_per_sec` and holds at most the tokens added in
+    /// `burst`.
+    pub fn new(rate_per_sec: u64, burst: Duration, now: SteadyTime) -> TokenBucket {
+        let capacity = rate_per_sec.saturating_mul(burst.num_milliseconds() as u64) / 1000;
+        TokenBucket {
+            rate_per_sec: rate_per_sec,
+            capacity: capacity,
+            micro_tokens: capacity.saturating_mul(1_000_000),
+            last_update: now,
+        }
+    }
+
+    /// The maximum number of tokens the bucket holds.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Whether `amount` tokens are available at time `now`.
+    pub fn has(&mut self, amount: u64, now: SteadyTime) -> bool {
+        self.update(now);
+        self.micro_tokens >= amount.saturating_mul(1_000_000)
+    }
+
+    /// Take `amount` tokens out of the bucket. The caller must check that they are available.
+    pub fn take(&mut self, amount: u64) {
+        self.micro_tokens = self.micro_tokens.saturating_sub(amount.saturating_mul(1_000_000));
+    }
+
+    /// The time until `amount` tokens will be available, or `None` if there will never be enough.
+    pub fn time_until(&mut self, amount: u64, now: SteadyTime) -> Option<Duration> {
+        if amount > self.capacity {
+            return None;
+        }
+        self.update(now);
+        let missing = amount.saturating_mul(1_000_000).saturating_sub(self.micro_tokens);
+        if missing == 0 {
+            return Some(Duration::zero());
+        }
+        // Tokens are added at `rate_per_sec` per second, i. e. at `rate_per_sec` micro-tokens per
+        // microsecond.
+        Some(Duration::microseconds(((missing + self.rate_per_sec - 1) / self.rate_per_sec) as i64))
+    }
+
+    fn update(&mut self, now: SteadyTime) {
+        let elapsed_micros = match (now - self.last_update).num_microseconds() {
+            Some(micros) if micros > 0 => micros as u64,
+            Some(_) => return,
+            None => u64::max_value(),
+        };
+        self.last_update = now;
+        let added = elapsed_micros.saturating_mul(self.rate_per_sec);
+        let max = self.capacity.saturating_mul(1_000_000);
+        self.micro_tokens = ::std::cmp::min(max, self.micro_tokens.saturating_add(added));
+    }
+}
+
+/// Limits the number of messages and bytes a peer can send per second.
+pub struct RateLimiter {
+    messages: TokenBucket,
+    bytes: TokenBucket,
+}
+
+impl RateLimiter {
+    /// Create a limiter that allows `message_rate` messages and `byte_rate` bytes per second, and
+    /// bursts of the messages and bytes allowed in `burst`.
+    pub fn new(message_rate: u64, byte_rate: u64, burst: Duration, now: SteadyTime) -> RateLimiter {
+        RateLimiter {
+            messages: TokenBucket::new(message_rate, burst, now),
+            bytes: TokenBucket::new(byte_rate, burst, now),
+        }
+    }
+
+    /// Record a message of `size` bytes if it is within the limits and return `Ok`. Otherwise
+    /// return the time after which it would be allowed, or `None` if it never will be.
+    pub fn allow(&mut self, size: u64, now: SteadyTime) -> Result<(), Option<Duration>> {
+        if self.messages.has(1, now) && self.bytes.has(size, now) {
+            self.messages.take(1);
+            self.bytes.take(size);
+            return Ok(());
+        }
+        match (self.messages.time_until(1, now), self.bytes.time_until(size, now)) {
+            (Some(message_wait), Some(byte_wait)) => {
+                Err(Some(::std::cmp::max(message_wait, byte_wait)))
+            }
+            _ => Err(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RateLimiter, TokenBucket};
+    use time::{Duration, SteadyTime};
+
+    #[test]
+    fn token_bucket() {
+        let start = SteadyTime::now();
+        let at_ms = |millis| start + Duration::milliseconds(millis);
+        let mut bucket = TokenBucket::new(10, Duration::seconds(2), at_ms(0));
+
+        // The bucket starts full, with 20 tokens.
+        assert!(bucket.has(20, at_ms(0)));
+        assert!(!bucket.has(21, at_ms(0)));
+        bucket.take(15);
+        assert!(bucket.has(5, at_ms(0)));
+        assert!(!bucket.has(6, at_ms(0)));
+        assert_eq!(Some(Duration::milliseconds(100)), bucket.time_until(6, at_ms(0)));
+
+        // One token is added every 100 ms, up to the capacity.
+        assert!(bucket.has(6, at_ms(100)));
+        assert!(bucket.has(20, at_ms(1500)));
+        assert!(!bucket.has(21, at_ms(5000)));
+        assert_eq!(None, bucket.time_until(21, at_ms(5000)));
+    }
+
+    #[test]
+    fn rate_limiter() {
+        let start = SteadyTime::now();
+        let at_ms = |millis| start + Duration::milliseconds(millis);
+        let mut limiter = RateLimiter::new(2, 1000, Duration::seconds(1), at_ms(0));
+
+        // Limited by the number of messages.
+        assert_eq!(Ok(()), limiter.allow(100, at_ms(0)));
+        assert_eq!(Ok(()), limiter.allow(100, at_ms(0)));
+        assert_eq!(Err(Some(Duration::milliseconds(500))), limiter.allow(100, at_ms(0)));
+        assert_eq!(Ok(()), limiter.allow(100, at_ms(500)));
+
+        // Limited by the number of bytes. Rejected messages don't use up any tokens.
+        assert_eq!(Ok(()), limiter.allow(500, at_ms(1500)));
+        assert_eq!(Err(Some(Duration::milliseconds(400))), limiter.allow(900, at_ms(1500)));
+        assert_eq!(Ok(()), limiter.allow(900, at_ms(1900)));
+
+        // Messages that exceed the burst size are never allowed.
+        assert_eq!(Err(None), limiter.allow(1001, at_ms(10_000)));
+    }
+}
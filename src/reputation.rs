@@ -0,0 +1,194 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use ip::IpAddr;
+use sodiumoxide::crypto::sign;
+use std::collections::HashMap;
+use std::hash::Hash;
+use time::{Duration, SteadyTime};
+
+/// Identifies a peer whose reputation is tracked: by its signing key once it has identified
+/// itself, otherwise by its IP address. Unlike the endpoint, neither changes when it reconnects.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum PeerId {
+    /// The peer's signing key.
+    Key(sign::PublicKey),
+    /// The peer's IP address.
+    Ip(IpAddr),
+}
+
+/// A protocol violation by a peer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Violation {
+    /// The peer sent bytes that could not be deserialised.
+    UndecodableMessage,
+    /// The peer sent a message with an invalid signature.
+    InvalidSignature,
+    /// The peer sent a message or data larger than the configured maximum.
+    OversizedMessage,
+    /// The peer sent messages faster than they could be checked.
+    Flooding,
+}
+
+impl Violation {
+    /// The amount by which the violation decreases the peer's score.
+    pub fn penalty(&self) -> i32 {
+        match *self {
+            Violation::UndecodableMessage => 20,
+            Violation::InvalidSignature => 50,
+            Violation::OversizedMessage => 50,
+            Violation::Flooding => 10,
+        }
+    }
+}
+
+/// What to do with a peer after it has been penalised.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Verdict {
+    /// The peer's score is still above the thresholds.
+    Keep,
+    /// The peer's score has reached the drop threshold: its connection should be dropped.
+    Drop,
+    /// The peer's score has reached the ban threshold: it should be disconnected and banned.
+    Ban,
+}
+
+/// Keeps a score for each peer, which starts at zero and is decreased by each protocol violation.
+///
+/// A peer's score is forgotten once it hasn't committed any violations for the `memory` duration.
+pub struct Reputation<K> {
+    scores: HashMap<K, (i32, SteadyTime)>,
+    drop_threshold: i32,
+    ban_threshold: i32,
+    memory: Duration,
+}
+
+impl<K: Clone + Eq + Hash> Reputation<K> {
+    /// Create a tracker that recommends dropping a peer once its score reaches `drop_threshold`,
+    /// and banning it once it reaches `ban_threshold`.
+    pub fn new(drop_threshold: i32, ban_threshold: i32, memory: Duration) -> Reputation<K> {
+        Reputation {
+            scores: HashMap::new(),
+            drop_threshold: drop_threshold,
+            ban_threshold: ban_threshold,
+            memory: memory,
+        }
+    }
+
+    /// Decrease the peer's score by the violation's penalty and return what to do with it.
+    pub fn penalise(&mut self, key: K, violation: Violation, now: SteadyTime) -> Verdict {
+        let memory = self.memory;
+        self.scores.retain(|_, &mut (_, last_violation)| now - last_violation < memory);
+        let score = {
+            let entry = self.scores.entry(key.clone()).or_insert((0, now));
+            entry.0 -= violation.penalty();
+            entry.1 = now;
+            entry.0
+        };
+        if score <= self.ban_threshold {
+            let _ = self.scores.remove(&key);
+            Verdict::Ban
+        } else if score <= self.drop_threshold {
+            Verdict::Drop
+        } else {
+            Verdict::Keep
+        }
+    }
+}
+
+/// A set of temporarily banned peers.
+pub struct BanList<K> {
+    bans: HashMap<K, SteadyTime>,
+    duration: Duration,
+}
+
+impl<K: Clone + Eq + Hash> BanList<K> {
+    /// Create an empty list whose bans last for `duration`.
+    pub fn new(duration: Duration) -> BanList<K> {
+        BanList {
+            bans: HashMap::new(),
+            duration: duration,
+        }
+    }
+
+    /// Ban the peer, or extend its ban if it is already banned.
+    pub fn ban(&mut self, key: K, now: SteadyTime) {
+        let _ = self.bans.insert(key, now + self.duration);
+    }
+
+    /// Whether the peer is currently banned. Expired bans are removed.
+    pub fn is_banned(&mut self, key: &K, now: SteadyTime) -> bool {
+        self.bans.retain(|_, &mut expiry| now < expiry);
+        self.bans.contains_key(key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BanList, Reputation, Verdict, Violation};
+    use time::{Duration, SteadyTime};
+
+    #[test]
+    fn violations_lead_to_drop_and_ban() {
+        let mut reputation = Reputation::new(-100, -150, Duration::minutes(10));
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+
+        assert_eq!(Verdict::Keep,
+                   reputation.penalise(1, Violation::InvalidSignature, at(0)));
+        assert_eq!(Verdict::Keep,
+                   reputation.penalise(2, Violation::UndecodableMessage, at(0)));
+        assert_eq!(Verdict::Drop,
+                   reputation.penalise(1, Violation::InvalidSignature, at(1)));
+        assert_eq!(Verdict::Ban,
+                   reputation.penalise(1, Violation::InvalidSignature, at(2)));
+        assert_eq!(Verdict::Keep,
+                   reputation.penalise(2, Violation::UndecodableMessage, at(2)));
+
+        // Banned peers start over.
+        assert_eq!(Verdict::Keep,
+                   reputation.penalise(1, Violation::InvalidSignature, at(3)));
+    }
+
+    #[test]
+    fn scores_are_forgotten() {
+        let mut reputation = Reputation::new(-100, -150, Duration::minutes(10));
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+
+        let _ = reputation.penalise(1, Violation::InvalidSignature, at(0));
+        let _ = reputation.penalise(1, Violation::InvalidSignature, at(300));
+        assert_eq!(Verdict::Keep,
+                   reputation.penalise(1, Violation::InvalidSignature, at(1000)));
+        assert_eq!(Verdict::Drop,
+                   reputation.penalise(1, Violation::InvalidSignature, at(1001)));
+    }
+
+    #[test]
+    fn bans_expire() {
+        let mut bans = BanList::new(Duration::minutes(10));
+        let start = SteadyTime::now();
+        let at = |secs| start + Duration::seconds(secs);
+
+        bans.ban(1, at(0));
+        assert!(bans.is_banned(&1, at(599)));
+        assert!(!bans.is_banned(&2, at(599)));
+        bans.ban(1, at(300));
+        assert!(bans.is_banned(&1, at(899)));
+        assert!(!bans.is_banned(&1, at(900)));
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use sodiumoxide::crypto::hash::sha512;
+
+/// The number of random bytes in a challenge seed.
+pub const SEED_SIZE: usize = 32;
+
+/// Returns a new random seed for a challenge.
+pub fn create_seed() -> Vec<u8> {
+    (0..SEED_SIZE).map(|_| ::rand::random::<u8>()).collect()
+}
+
+/// Returns the data of the given size a proof for `seed` must contain.
+pub fn create_data(seed: &[u8], size: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(size);
+    let mut block = sha512::hash(seed);
+    while data.len() < size {
+        let remaining = size - data.len();
+        data.extend(block.0.iter().take(remaining));
+        block = sha512::hash(&block.0);
+    }
+    data
+}
+
+/// Returns the smallest nonce that completes a proof for `seed` and `data` with the given
+/// difficulty.
+pub fn solve(seed: &[u8], data: &[u8], difficulty: u8) -> u64 {
+    let digest = proof_digest(seed, data);
+    let mut nonce = 0u64;
+    while !check_nonce(&digest, nonce, difficulty) {
+        nonce += 1;
+    }
+    nonce
+}
+
+/// Returns whether `data` and `nonce` constitute a valid proof for the given challenge.
+///
+/// A proof consists of the `size` bytes of data derived from the seed, which the joining node has
+/// to transmit, and a nonce such that the SHA512 hash of the nonce and the hash of seed and data
+/// starts with `difficulty` zero bits, which the joining node has to search for.
+pub fn validate(seed: &[u8], size: usize, difficulty: u8, data: &[u8], nonce: u64) -> bool {
+    data.len() == size && data == &create_data(seed, size)[..] &&
+    check_nonce(&proof_digest(seed, data), nonce, difficulty)
+}
+
+// Hashing seed and data once means each attempted nonce only costs hashing 72 bytes, independent
+// of the data size.
+fn proof_digest(seed: &[u8], data: &[u8]) -> sha512::Digest {
+    let mut input = Vec::with_capacity(seed.len() + data.len());
+    input.extend(seed.iter());
+    input.extend(data.iter());
+    sha512::hash(&input)
+}
+
+fn check_nonce(digest: &sha512::Digest, nonce: u64, difficulty: u8) -> bool {
+    let mut input = Vec::with_capacity(sha512::DIGESTBYTES + 8);
+    input.extend(digest.0.iter());
+    for i in 0..8 {
+        input.push((nonce >> (8 * i)) as u8);
+    }
+    leading_zero_bits(&sha512::hash(&input).0) >= difficulty as usize
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> usize {
+    let mut count = 0;
+    for byte in bytes {
+        if *byte == 0 {
+            count += 8;
+        } else {
+            return count + byte.leading_zeros() as usize;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn leading_zero_bits() {
+        assert_eq!(0, super::leading_zero_bits(&[0x80, 0]));
+        assert_eq!(7, super::leading_zero_bits(&[0x01, 0xff]));
+        assert_eq!(12, super::leading_zero_bits(&[0, 0x08]));
+        assert_eq!(16, super::leading_zero_bits(&[0, 0]));
+    }
+
+    #[test]
+    fn create_data() {
+        let seed = super::create_seed();
+        assert_eq!(super::SEED_SIZE, seed.len());
+        let data = super::create_data(&seed, 1000);
+        assert_eq!(1000, data.len());
+        assert_eq!(data, super::create_data(&seed, 1000));
+        assert_eq!(&data[..100], &super::create_data(&seed, 100)[..]);
+        assert!(super::create_data(&seed, 0).is_empty());
+        assert!(data != super::create_data(&super::create_seed(), 1000));
+    }
+
+    #[test]
+    fn solve_and_validate() {
+        let seed = super::create_seed();
+        let size = 200;
+        let difficulty = 8;
+        let data = super::create_data(&seed, size);
+        let nonce = super::solve(&seed, &data, difficulty);
+        assert!(super::validate(&seed, size, difficulty, &data, nonce));
+
+        // The nonce is the smallest solution, so any smaller one must be rejected.
+        if nonce > 0 {
+            assert!(!super::validate(&seed, size, difficulty, &data, nonce - 1));
+        }
+
+        // Wrong size or data.
+        assert!(!super::validate(&seed, size + 1, difficulty, &data, nonce));
+        let mut wrong_data = data.clone();
+        wrong_data[0] ^= 1;
+        assert!(!super::validate(&seed, size, difficulty, &wrong_data, nonce));
+
+        // Another seed.
+        let other_seed = super::create_seed();
+        let other_data = super::create_data(&other_seed, size);
+        assert!(!super::validate(&other_seed, size, difficulty, &data, nonce));
+        assert!(!super::validate(&seed, size, difficulty, &other_data, nonce));
+
+        // Zero difficulty accepts any nonce.
+        assert!(super::validate(&seed, size, 0, &data, 12345));
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use authority::Authority;
+use messages::{RequestContent, RequestMessage, ResponseContent, ResponseMessage, RoutingMessage};
+use rate_limit::TokenBucket;
+use std::collections::VecDeque;
+use time::{Duration, SteadyTime};
+
+/// The priority class of an outgoing message. Messages in the classes listed first are sent first.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Priority {
+    /// Messages that maintain connections and the routing table.
+    Control,
+    /// Messages from group authorities, which need to reach a quorum.
+    GroupConsensus,
+    /// Data requests and responses from clients and individual nodes.
+    ClientData,
+    /// Data served from a node's cache.
+    Cache,
+}
+
+const PRIORITY_COUNT: usize = 4;
+
+impl Priority {
+    /// The priority class of the given routing message.
+    pub fn of(routing_msg: &RoutingMessage) -> Priority {
+        match *routing_msg {
+            RoutingMessage::Request(RequestMessage { ref content, ref src, .. }) => {
+                match *content {
+                    RequestContent::Refresh(..) => Priority::GroupConsensus,
+                    RequestContent::Get(..) |
+                    RequestContent::Put(..) |
+                    RequestContent::Post(..) |
+                    RequestContent::Delete(..) => Priority::of_data(src),
+                    _ => Priority::Control,
+                }
+            }
+            // Only a node that has the data cached responds directly to the client.
+            RoutingMessage::Response(ResponseMessage {
+                content: ResponseContent::GetSuccess(..),
+                src: Authority::ManagedNode(_),
+                dst: Authority::Client { .. },
+            }) => Priority::Cache,
+            RoutingMessage::Response(ResponseMessage { ref content, ref src, .. }) => {
+                match *content {
+                    ResponseContent::GetSuccess(..) |
+                    ResponseContent::PutSuccess(..) |
+                    ResponseContent::PostSuccess(..) |
+                    ResponseContent::DeleteSuccess(..) |
+                    ResponseContent::GetFailure { .. } |
+                    ResponseContent::PutFailure { .. } |
+                    ResponseContent::PostFailure { .. } |
+                    ResponseContent::DeleteFailure { .. } => Priority::of_data(src),
+                    _ => Priority::Control,
+                }
+            }
+        }
+    }
+
+    fn of_data(src: &Authority) -> Priority {
+        if src.is_group() {
+            Priority::GroupConsensus
+        } else {
+            Priority::ClientData
+        }
+    }
+}
+
+/// The outgoing messages to a single peer.
+///
+/// Messages are queued up to a maximum total size, and sent in the order of their priority at a
+/// limited byte rate.
+pub struct SendQueue {
+    // One queue per priority class, the highest priority first.
+    queues: Vec<VecDeque<Vec<u8>>>,
+    len: usize,
+    max_len: usize,
+    bucket: TokenBucket,
+}
+
+impl SendQueue {
+    /// Create a queue that holds up to `max_len` bytes and sends `byte_rate` bytes per second, in
+    /// bursts of up to the bytes allowed in `burst`. The rate and burst must not be zero.
+    pub fn new(max_len: usize, byte_rate: u64, burst: Duration, now: SteadyTime) -> SendQueue {
+        SendQueue {
+            queues: (0..PRIORITY_COUNT).map(|_| VecDeque::new()).collect(),
+            len: 0,
+            max_len: max_len,
+            bucket: TokenBucket::new(byte_rate, burst, now),
+        }
+    }
+
+    /// Add a message, dropping queued messages of lower priority if necessary to make room for it.
+    /// Return `false` if the queue is too full even then.
+    pub fn push(&mut self, priority: Priority, message: Vec<u8>) -> bool {
+        let index = priority as usize;
+        let droppable = self.queues[(index + 1)..]
+                            .iter()
+                            .flat_map(|queue| queue.iter())
+                            .fold(0, |total, queued| total + queued.len());
+        if self.len - droppable + message.len() > self.max_len {
+            return false;
+        }
+        // Drop the most recent messages of the lowest priority first.
+        for queue in self.queues[(index + 1)..].iter_mut().rev() {
+            while self.len + message.len() > self.max_len {
+                match queue.pop_back() {
+                    Some(dropped) => self.len -= dropped.len(),
+                    None => break,
+                }
+            }
+        }
+        self.len += message.len();
+        self.queues[index].push_back(message);
+        true
+    }
+
+    /// Remove and return the next message, if the byte rate allows sending it at time `now`.
+    ///
+    /// Messages larger than a burst are sent whenever the full burst is available.
+    pub fn pop(&mut self, now: SteadyTime) -> Option<Vec<u8>> {
+        let size = match self.queues.iter().filter_map(|queue| queue.front()).next() {
+            Some(message) => message.len() as u64,
+            None => return None,
+        };
+        let required = ::std::cmp::min(size, self.bucket.capacity());
+        if !self.bucket.has(required, now) {
+            return None;
+        }
+        self.bucket.take(size);
+        let message = self.queues.iter_mut().filter_map(|queue| queue.pop_front()).next();
+        if let Some(ref message) = message {
+            self.len -= message.len();
+        }
+        message
+    }
+
+    /// The time until the byte rate allows sending the next message, or `None` if the queue is
+    /// empty.
+    pub fn time_until_next(&mut self, now: SteadyTime) -> Option<Duration> {
+        let size = match self.queues.iter().filter_map(|queue| queue.front()).next() {
+            Some(message) => message.len() as u64,
+            None => return None,
+        };
+        let required = ::std::cmp::min(size, self.bucket.capacity());
+        self.bucket.time_until(required, now)
+    }
+
+    /// Whether there are no messages waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Priority, SendQueue};
+    use time::{Duration, SteadyTime};
+
+    #[test]
+    fn sends_in_order_of_priority() {
+        let now = SteadyTime::now();
+        let mut queue = SendQueue::new(1000, 1000, Duration::seconds(1), now);
+        assert!(queue.push(Priority::Cache, vec![4]));
+        assert!(queue.push(Priority::ClientData, vec![3]));
+        assert!(queue.push(Priority::Control, vec![1]));
+        assert!(queue.push(Priority::GroupConsensus, vec![2]));
+        assert!(queue.push(Priority::Control, vec![1, 1]));
+
+        let sent = (0..5).filter_map(|_| queue.pop(now)).collect::<Vec<_>>();
+        assert_eq!(vec![vec![1], vec![1, 1], vec![2], vec![3], vec![4]], sent);
+        assert!(queue.is_empty());
+        assert_eq!(None, queue.pop(now));
+    }
+
+    #[test]
+    fn drops_lower_priority_messages_when_full() {
+        let now = SteadyTime::now();
+        let mut queue = SendQueue::new(10, 1, Duration::seconds(1), now);
+        // Use up the burst, so that nothing can be sent.
+        assert!(queue.push(Priority::Control, vec![0]));
+        assert!(queue.pop(now).is_some());
+
+        assert!(queue.push(Priority::Cache, vec![4; 4]));
+        assert!(queue.push(Priority::ClientData, vec![3; 3]));
+        assert!(queue.push(Priority::ClientData, vec![3; 3]));
+        // No room, and nothing of lower priority to drop.
+        assert!(!queue.push(Priority::Cache, vec![4]));
+        // The cached data and one client message are dropped.
+        assert!(queue.push(Priority::GroupConsensus, vec![2; 5]));
+        assert!(queue.push(Priority::Control, vec![1; 2]));
+
+        let sent = (0..4)
+                       .filter_map(|i| queue.pop(now + Duration::seconds(10 * (i + 1))))
+                       .collect::<Vec<_>>();
+        assert_eq!(vec![vec![1; 2], vec![2; 5], vec![3; 3]], sent);
+    }
+
+    #[test]
+    fn limits_byte_rate() {
+        let start = SteadyTime::now();
+        let at_ms = |millis| start + Duration::milliseconds(millis);
+        let mut queue = SendQueue::new(1000, 100, Duration::seconds(1), at_ms(0));
+        assert!(queue.push(Priority::ClientData, vec![0; 60]));
+        assert!(queue.push(Priority::ClientData, vec![0; 60]));
+        assert!(queue.push(Priority::ClientData, vec![0; 300]));
+
+        assert!(queue.pop(at_ms(0)).is_some());
+        assert_eq!(None, queue.pop(at_ms(0)));
+        assert_eq!(Some(Duration::milliseconds(200)), queue.time_until_next(at_ms(0)));
+        assert!(queue.pop(at_ms(200)).is_some());
+        // Larger than the burst: sent once the bucket is full.
+        assert_eq!(Some(Duration::milliseconds(1000)), queue.time_until_next(at_ms(200)));
+        assert_eq!(None, queue.pop(at_ms(1100)));
+        assert!(queue.pop(at_ms(1200)).is_some());
+        assert!(queue.is_empty());
+        assert_eq!(None, queue.time_until_next(at_ms(1200)));
+    }
+}
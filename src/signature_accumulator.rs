@@ -0,0 +1,219 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use digest_filter::PeerShares;
+use id::PublicId;
+use lru_time_cache::LruCache;
+use sodiumoxide::crypto::hash::sha512;
+use sodiumoxide::crypto::sign;
+use time::Duration;
+
+/// Collects the signatures of the members of a group authority that sent the same message until
+/// the quorum is reached.
+///
+/// Messages are identified by their digest, so the memory used doesn't depend on their size. A
+/// message's signatures are forgotten after the expiry duration, or when the capacity is exceeded,
+/// whichever comes first. Each peer we receive signatures from can only have delivered a share of
+/// the pending messages, so that a peer sending us many unique messages forgets its own
+/// signatures early rather than everyone else's. Peers are identified by the keys of the
+/// connections we received the signatures on, since anyone can sign with a new key.
+pub struct SignatureAccumulator {
+    quorum_size: usize,
+    // The signatures of each message, and the keys of the peers that delivered them
+    signatures: LruCache<sha512::Digest, Vec<(PublicId, sign::Signature, sign::PublicKey)>>,
+    capacity: usize,
+    peer_shares: PeerShares,
+    early_evictions: u64,
+}
+
+impl SignatureAccumulator {
+    /// Create an accumulator for up to `capacity` messages, each of which is kept for the `expiry`
+    /// duration, and for up to `peer_share` pending messages delivered by a single peer.
+    pub fn new(quorum_size: usize,
+               expiry: Duration,
+               capacity: usize,
+               peer_share: usize)
+               -> SignatureAccumulator {
+        SignatureAccumulator {
+            quorum_size: quorum_size,
+            signatures: LruCache::with_expiry_duration_and_capacity(expiry, capacity),
+            capacity: capacity,
+            peer_shares: PeerShares::new(expiry, capacity, peer_share),
+            early_evictions: 0,
+        }
+    }
+
+    /// Set the number of signatures required for a quorum.
+    pub fn set_quorum_size(&mut self, quorum_size: usize) {
+        self.quorum_size = quorum_size;
+    }
+
+    /// Add a group member's signature of the message with the given digest, received from the
+    /// peer with the signing key `peer`. Once the quorum is reached, return all the signatures and
+    /// forget the message.
+    pub fn add(&mut self,
+               digest: sha512::Digest,
+               public_id: PublicId,
+               signature: sign::Signature,
+               peer: sign::PublicKey)
+               -> Option<Vec<(PublicId, sign::Signature)>> {
+        let mut signatures = self.signatures.remove(&digest).unwrap_or_else(Vec::new);
+        if signatures.iter().all(|&(ref signer, _, _)| *signer != public_id) {
+            if signatures.iter().all(|&(_, _, ref sender)| *sender != peer) {
+                let oldest = {
+                    let cached = &self.signatures;
+                    self.peer_shares.add(peer, digest, |digest| cached.contains_key(digest))
+                };
+                if let Some(oldest) = oldest {
+                    self.remove_signatures(&oldest, &peer);
+                }
+            }
+            signatures.push((public_id, signature, peer));
+        }
+        if signatures.len() >= self.quorum_size {
+            for &(_, _, ref sender) in &signatures {
+                self.peer_shares.remove(sender, &digest);
+            }
+            return Some(signatures.into_iter()
+                                  .map(|(public_id, signature, _)| (public_id, signature))
+                                  .collect());
+        }
+        if self.signatures.len() >= self.capacity {
+            debug!("Signature accumulator is full, forgetting the oldest message before it \
+                    expires.");
+            self.early_evictions += 1;
+        }
+        let _ = self.signatures.insert(digest, signatures);
+        None
+    }
+
+    /// The number of messages or signatures forgotten before they expired, to stay within the
+    /// capacity or a peer's share of it.
+    pub fn early_evictions(&self) -> u64 {
+        self.early_evictions
+    }
+
+    // Removes the signatures of the message with the given digest that were received from `peer`.
+    fn remove_signatures(&mut self, digest: &sha512::Digest, peer: &sign::PublicKey) {
+        let remaining = match self.signatures.get_mut(digest) {
+            Some(signatures) => {
+                let count = signatures.len();
+                signatures.retain(|&(_, _, ref sender)| sender != peer);
+                if signatures.len() == count {
+                    return;
+                }
+                signatures.len()
+            }
+            None => return,
+        };
+        self.early_evictions += 1;
+        if remaining == 0 {
+            let _ = self.signatures.remove(digest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SignatureAccumulator;
+    use id::FullId;
+    use sodiumoxide::crypto::hash::sha512;
+    use sodiumoxide::crypto::sign;
+    use time::Duration;
+
+    #[test]
+    fn accumulate() {
+        let mut accumulator = SignatureAccumulator::new(3, Duration::minutes(5), 10, 10);
+        let digest = sha512::hash(b"message");
+        let full_ids = (0..3).map(|_| FullId::new()).collect::<Vec<_>>();
+        let peer = sign::gen_keypair().0;
+        let signature = |full_id: &FullId| {
+            sign::sign_detached(b"message", full_id.signing_private_key())
+        };
+
+        assert!(accumulator.add(digest,
+                                full_ids[0].public_id().clone(),
+                                signature(&full_ids[0]),
+                                peer)
+                           .is_none());
+        // A duplicate signer doesn't count towards the quorum.
+        assert!(accumulator.add(digest,
+                                full_ids[0].public_id().clone(),
+                                signature(&full_ids[0]),
+                                peer)
+                           .is_none());
+        assert!(accumulator.add(digest,
+                                full_ids[1].public_id().clone(),
+                                signature(&full_ids[1]),
+                                peer)
+                           .is_none());
+        let signatures = unwrap_option!(accumulator.add(digest,
+                                                        full_ids[2].public_id().clone(),
+                                                        signature(&full_ids[2]),
+                                                        peer),
+                                        "Quorum not reached");
+        assert_eq!(3, signatures.len());
+        assert_eq!(0, accumulator.signatures.len());
+    }
+
+    #[test]
+    fn bounded_under_sustained_load() {
+        let capacity = 100;
+        let mut accumulator =
+            SignatureAccumulator::new(2, Duration::minutes(5), capacity, capacity);
+        let full_id = FullId::new();
+        let peer = *full_id.public_id().signing_public_key();
+        for i in 0..(10 * capacity) {
+            let digest = sha512::hash(i.to_string().as_bytes());
+            let signature = sign::sign_detached(&digest.0, full_id.signing_private_key());
+            assert!(accumulator.add(digest, full_id.public_id().clone(), signature, peer)
+                               .is_none());
+            assert!(accumulator.signatures.len() <= capacity);
+        }
+        assert_eq!(9 * capacity as u64, accumulator.early_evictions());
+    }
+
+    #[test]
+    fn flooding_peer_evicts_its_own_signatures() {
+        let capacity = 100;
+        let mut accumulator =
+            SignatureAccumulator::new(2, Duration::minutes(5), capacity, capacity / 2);
+        let full_ids = (0..2).map(|_| FullId::new()).collect::<Vec<_>>();
+        let honest_peer = sign::gen_keypair().0;
+        let flooding_peer = sign::gen_keypair().0;
+        let digest = sha512::hash(b"message");
+        let signature = sign::sign_detached(&digest.0, full_ids[0].signing_private_key());
+        assert!(accumulator.add(digest, full_ids[0].public_id().clone(), signature, honest_peer)
+                           .is_none());
+        // The flooding peer signs each message with a new key.
+        for i in 0..(10 * capacity) {
+            let flood_digest = sha512::hash(i.to_string().as_bytes());
+            let signer = FullId::new();
+            let signature = sign::sign_detached(&flood_digest.0, signer.signing_private_key());
+            assert!(accumulator.add(flood_digest,
+                                    signer.public_id().clone(),
+                                    signature,
+                                    flooding_peer)
+                               .is_none());
+            assert!(accumulator.signatures.len() <= capacity / 2 + 1);
+        }
+        // The message being accumulated before the flood still reaches the quorum.
+        let signature = sign::sign_detached(&digest.0, full_ids[1].signing_private_key());
+        assert!(accumulator.add(digest, full_ids[1].public_id().clone(), signature, honest_peer)
+                           .is_some());
+    }
+}
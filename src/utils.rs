@@ -15,8 +15,24 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use crust::Endpoint;
+use ip::IpAddr;
+use maidsafe_utilities::serialisation;
+use sodiumoxide::crypto::{box_, sign};
+use std::net::SocketAddr;
 use xor_name::XorName;
 
+use error::RoutingError;
+use id::PublicId;
+
+/// Return the IP address of the given socket address.
+pub fn ip_from_socketaddr(addr: SocketAddr) -> IpAddr {
+    match addr {
+        SocketAddr::V4(address) => IpAddr::V4(*address.ip()),
+        SocketAddr::V6(address) => IpAddr::V6(*address.ip()),
+    }
+}
+
 /// Format a vector of bytes as a hexadecimal number, ellipsizing all but the first and last three.
 ///
 /// For three bytes with values 1, 2, 3, the output will be "BYTES:010203". For more than six
@@ -57,9 +73,21 @@ pub fn get_debug_id<V: AsRef<[u8]>>(input: V) -> String {
 /// In case of only one close node provided (in initial network setup scenario):
 ///
 /// [original_name, 1st closest node id]
-pub fn calculate_relocated_name(mut close_nodes: Vec<XorName>,
+pub fn calculate_relocated_name(close_nodes: Vec<XorName>,
                                 original_name: &XorName)
                                 -> Result<XorName, ::error::RoutingError> {
+    let relocation_nodes = try!(relocation_close_nodes(close_nodes, original_name));
+    Ok(hash_relocated_name(original_name, &relocation_nodes))
+}
+
+/// Select the close nodes from which a relocated name is computed.
+///
+/// These are the (at most) two nodes closest to `original_name`, ordered by their distance to it.
+/// They are sent along with the relocated name, so that the recipients can check the computation
+/// using `verify_relocated_name`.
+pub fn relocation_close_nodes(mut close_nodes: Vec<XorName>,
+                              original_name: &XorName)
+                              -> Result<Vec<XorName>, ::error::RoutingError> {
     if close_nodes.is_empty() {
         return Err(::error::RoutingError::RoutingTableEmpty);
     }
@@ -70,22 +98,162 @@ pub fn calculate_relocated_name(mut close_nodes: Vec<XorName>,
             ::std::cmp::Ordering::Greater
         }
     });
+    close_nodes.dedup();
     close_nodes.truncate(2usize);
-    close_nodes.insert(0, original_name.clone());
+    Ok(close_nodes)
+}
+
+/// Check that `relocated_name` has been computed from `original_name` and the given close nodes.
+///
+/// The close nodes must be the ones returned by `relocation_close_nodes`: one or two distinct
+/// names, ordered by their distance to `original_name`.
+pub fn verify_relocated_name(original_name: &XorName,
+                             close_nodes: &[XorName],
+                             relocated_name: &XorName)
+                             -> Result<(), ::error::RoutingError> {
+    if close_nodes.is_empty() || close_nodes.len() > 2 {
+        return Err(::error::RoutingError::InvalidRelocation);
+    }
+    if close_nodes.len() == 2 &&
+       !::xor_name::closer_to_target(&close_nodes[0], &close_nodes[1], original_name) {
+        return Err(::error::RoutingError::InvalidRelocation);
+    }
+    if hash_relocated_name(original_name, close_nodes) != *relocated_name {
+        return Err(::error::RoutingError::InvalidRelocation);
+    }
+    Ok(())
+}
+
+/// Returns the number of leading bits `name` has in common with `our_name`.
+///
+/// This is the index of the routing table bucket `name` belongs to, in the routing table of the
+/// node called `our_name`.
+pub fn bucket_index(our_name: &XorName, name: &XorName) -> usize {
+    for (i, (our_byte, byte)) in our_name.0.iter().zip(name.0.iter()).enumerate() {
+        let difference = our_byte ^ byte;
+        if difference != 0 {
+            return 8 * i + difference.leading_zeros() as usize;
+        }
+    }
+    8 * our_name.0.len()
+}
+
+/// Returns a random name in the routing table bucket with the given index, i. e. a name that has
+/// exactly `index` leading bits in common with `our_name`.
+///
+/// `index` must be less than the number of bits in a name.
+pub fn random_name_in_bucket(our_name: &XorName, index: usize) -> XorName {
+    let mut name: XorName = ::rand::random();
+    let byte = index / 8;
+    let bit = 0x80u8 >> (index % 8);
+    let prefix_mask = !(0xffu8 >> (index % 8));
+    for i in 0..byte {
+        name.0[i] = our_name.0[i];
+    }
+    name.0[byte] = (our_name.0[byte] & prefix_mask) | (!our_name.0[byte] & bit) |
+                   (name.0[byte] & !(prefix_mask | bit));
+    name
+}
+
+/// Estimate the number of nodes in the network, including ourselves, from the names of the nodes
+/// closest to us.
+///
+/// In a network of `n` nodes with uniformly distributed names, the `i`-th closest node to us is
+/// expected to be at a distance of `i / n` of the address space. The estimate is the value of `n`
+/// that best fits the observed distances.
+pub fn estimate_network_size(our_name: &XorName, close_names: &[XorName]) -> u64 {
+    let mut distances = close_names.iter()
+                                   .map(|name| {
+                                       // The first 8 bytes of the distance, as a fraction of the
+                                       // address space.
+                                       let mut distance = 0u64;
+                                       for i in 0..8 {
+                                           distance = (distance << 8) |
+                                                      (our_name.0[i] ^ name.0[i]) as u64;
+                                       }
+                                       distance as f64 / 18446744073709551616.0
+                                   })
+                                   .collect::<Vec<_>>();
+    distances.sort_by(|lhs, rhs| lhs.partial_cmp(rhs).unwrap_or(::std::cmp::Ordering::Equal));
+    let mut sum_squares = 0.0;
+    let mut sum_products = 0.0;
+    for (i, distance) in distances.iter().enumerate() {
+        let rank = (i + 1) as f64;
+        sum_squares += rank * rank;
+        sum_products += rank * distance;
+    }
+    if sum_products <= 0.0 {
+        return close_names.len() as u64 + 1;
+    }
+    let estimate = (sum_squares / sum_products).round() as u64;
+    ::std::cmp::max(estimate, close_names.len() as u64 + 1)
+}
+
+/// Deserialise a `PublicId` sent in a `ClientIdentify` or `NodeIdentify` message and verify that
+/// `signature` is its owner's signature of the serialised bytes.
+pub fn verify_signed_public_id(serialised_public_id: &[u8],
+                               signature: &sign::Signature)
+                               -> Result<PublicId, RoutingError> {
+    let public_id: PublicId = try!(serialisation::deserialise(serialised_public_id));
+    if sign::verify_detached(signature,
+                             serialised_public_id,
+                             public_id.signing_public_key()) {
+        Ok(public_id)
+    } else {
+        Err(RoutingError::FailedSignature)
+    }
+}
+
+/// Serialise and encrypt `endpoints` for the node with the encrypting key `their_public_key`.
+///
+/// Returns the encrypted endpoints and the nonce, as sent in `RequestContent::Endpoints` and
+/// `ConnectionInfo`.
+pub fn encrypt_endpoints(endpoints: &[Endpoint],
+                         their_public_key: &box_::PublicKey,
+                         our_secret_key: &box_::SecretKey)
+                         -> Result<(Vec<u8>, box_::Nonce), RoutingError> {
+    let encoded_endpoints = try!(serialisation::serialise(&endpoints));
+    let nonce = box_::gen_nonce();
+    let encrypted_endpoints = box_::seal(&encoded_endpoints,
+                                         &nonce,
+                                         their_public_key,
+                                         our_secret_key);
+    Ok((encrypted_endpoints, nonce))
+}
+
+/// Decrypt and deserialise the endpoints sent by the node with the encrypting key
+/// `their_public_key`.
+pub fn decrypt_endpoints(encrypted_endpoints: &[u8],
+                         nonce_bytes: [u8; box_::NONCEBYTES],
+                         their_public_key: &box_::PublicKey,
+                         our_secret_key: &box_::SecretKey)
+                         -> Result<Vec<Endpoint>, RoutingError> {
+    let serialised_endpoints = try!(box_::open(encrypted_endpoints,
+                                               &box_::Nonce(nonce_bytes),
+                                               their_public_key,
+                                               our_secret_key)
+                                        .map_err(|()| RoutingError::AsymmetricDecryptionFailure));
+    Ok(try!(serialisation::deserialise(&serialised_endpoints)))
+}
 
+/// Compute the relocated name from `original_name` and the close nodes already selected by
+/// `relocation_close_nodes`.
+pub fn hash_relocated_name(original_name: &XorName, close_nodes: &[XorName]) -> XorName {
     let mut combined: Vec<u8> = Vec::new();
-    for node_id in close_nodes {
+    for node_id in Some(original_name).into_iter().chain(close_nodes.iter()) {
         for i in node_id.get_id().iter() {
             combined.push(*i);
         }
     }
-    Ok(XorName(::sodiumoxide::crypto::hash::sha512::hash(&combined).0))
+    XorName(::sodiumoxide::crypto::hash::sha512::hash(&combined).0)
 }
 
 #[cfg(test)]
 mod test {
     extern crate rand;
 
+    use crust::Endpoint;
+    use sodiumoxide::crypto::box_;
     use xor_name::XorName;
 
 
@@ -168,4 +336,145 @@ mod test {
             XorName(::sodiumoxide::crypto::hash::sha512::hash(&invalid_combined).0);
         assert!(invalid_relocated_name != actual_relocated_name);
     }
+
+    #[test]
+    fn bucket_index() {
+        let our_name: XorName = rand::random();
+        assert_eq!(512, super::bucket_index(&our_name, &our_name));
+        for i in 0..512 {
+            let mut name = our_name;
+            name.0[i / 8] ^= 0x80 >> (i % 8);
+            assert_eq!(i, super::bucket_index(&our_name, &name));
+            assert_eq!(i, super::bucket_index(&name, &our_name));
+            // Bits after the first differing one don't matter.
+            if i < 511 {
+                name.0[63] ^= 1;
+                assert_eq!(i, super::bucket_index(&our_name, &name));
+            }
+        }
+    }
+
+    #[test]
+    fn random_name_in_bucket() {
+        let our_name: XorName = rand::random();
+        for index in 0..512 {
+            let name = super::random_name_in_bucket(&our_name, index);
+            assert_eq!(index, super::bucket_index(&our_name, &name));
+        }
+    }
+
+    #[test]
+    fn estimate_network_size() {
+        let our_name: XorName = rand::random();
+        assert_eq!(1, super::estimate_network_size(&our_name, &[]));
+
+        // The estimate can't be smaller than the number of nodes we know.
+        let mut far_name = our_name;
+        far_name.0[0] ^= 0xff;
+        assert_eq!(4, super::estimate_network_size(&our_name, &[far_name; 3]));
+
+        // Nodes at 1/8, 1/4 and 1/2 of the address space.
+        let names = (0..3)
+                        .map(|i| {
+                            let mut name = our_name;
+                            name.0[0] ^= 0x20 << i;
+                            name
+                        })
+                        .collect::<Vec<_>>();
+        assert_eq!(7, super::estimate_network_size(&our_name, &names));
+
+        for &network_size in &[100, 2000] {
+            let trials = 30;
+            let mut sum = 0;
+            for _ in 0..trials {
+                let mut names = (0..network_size - 1).map(|_| rand::random()).collect::<Vec<_>>();
+                names.sort_by(|a, b| {
+                    if ::xor_name::closer_to_target(&a, &b, &our_name) {
+                        ::std::cmp::Ordering::Less
+                    } else {
+                        ::std::cmp::Ordering::Greater
+                    }
+                });
+                names.truncate(::kademlia_routing_table::GROUP_SIZE);
+                sum += super::estimate_network_size(&our_name, &names);
+            }
+            let average = sum / trials;
+            assert!(average > network_size / 2 && average < network_size * 2,
+                    "Estimated {} instead of {}",
+                    average,
+                    network_size);
+        }
+    }
+
+    #[test]
+    fn verify_relocated_name() {
+        let original_name: XorName = rand::random();
+        let mut close_nodes: Vec<XorName> = Vec::new();
+        for _ in 0..::kademlia_routing_table::GROUP_SIZE {
+            close_nodes.push(rand::random());
+        }
+        let relocated_name =
+            unwrap_result!(super::calculate_relocated_name(close_nodes.clone(), &original_name));
+        let relocation_nodes =
+            unwrap_result!(super::relocation_close_nodes(close_nodes.clone(), &original_name));
+        assert_eq!(2, relocation_nodes.len());
+        assert!(super::verify_relocated_name(&original_name, &relocation_nodes, &relocated_name)
+                    .is_ok());
+
+        // wrong order
+        let mut reversed_nodes = relocation_nodes.clone();
+        reversed_nodes.reverse();
+        assert!(super::verify_relocated_name(&original_name, &reversed_nodes, &relocated_name)
+                    .is_err());
+
+        // only one of the two nodes
+        assert!(super::verify_relocated_name(&original_name,
+                                             &relocation_nodes[..1],
+                                             &relocated_name)
+                    .is_err());
+
+        // too many nodes
+        assert!(super::verify_relocated_name(&original_name, &close_nodes, &relocated_name)
+                    .is_err());
+
+        // different original name
+        let other_name: XorName = rand::random();
+        assert!(super::verify_relocated_name(&other_name, &relocation_nodes, &relocated_name)
+                    .is_err());
+
+        // single close node
+        let single_node = vec![close_nodes[0]];
+        let relocated_name_single_node =
+            unwrap_result!(super::calculate_relocated_name(single_node.clone(), &original_name));
+        assert!(super::verify_relocated_name(&original_name,
+                                             &single_node,
+                                             &relocated_name_single_node)
+                    .is_ok());
+        assert!(super::verify_relocated_name(&original_name, &[], &relocated_name_single_node)
+                    .is_err());
+    }
+
+    #[test]
+    fn encrypt_endpoints() {
+        let endpoints = vec![Endpoint::Tcp(unwrap_result!("127.0.0.1:5483".parse()))];
+        let (our_public_key, our_secret_key) = box_::gen_keypair();
+        let (their_public_key, their_secret_key) = box_::gen_keypair();
+        let encrypted = super::encrypt_endpoints(&endpoints, &their_public_key, &our_secret_key);
+        let (encrypted_endpoints, nonce) = unwrap_result!(encrypted);
+        assert_eq!(endpoints,
+                   unwrap_result!(super::decrypt_endpoints(&encrypted_endpoints,
+                                                           nonce.0,
+                                                           &our_public_key,
+                                                           &their_secret_key)));
+        assert!(super::decrypt_endpoints(&encrypted_endpoints,
+                                         nonce.0,
+                                         &their_public_key,
+                                         &their_secret_key)
+                    .is_err());
+        assert!(super::decrypt_endpoints(&encrypted_endpoints[1..],
+                                         nonce.0,
+                                         &our_public_key,
+                                         &their_secret_key)
+                    .is_err());
+    }
 }
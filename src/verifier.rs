@@ -0,0 +1,342 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use action::Action;
+use crust;
+use error::RoutingError;
+use id::PublicId;
+use maidsafe_utilities::event_sender::MaidSafeEventCategory;
+use maidsafe_utilities::serialisation;
+use maidsafe_utilities::thread::RaiiThreadJoiner;
+use messages::{DirectMessage, HopMessage, Message, SignedMessage};
+use sodiumoxide::crypto::{box_, sign};
+use std::collections::HashMap;
+use std::sync::mpsc;
+use types::RoutingActionSender;
+use utils;
+
+/// The decoded content of an incoming message.
+pub enum CheckedContent {
+    /// A message sent directly by the peer.
+    Direct(DirectMessage),
+    /// A `ClientIdentify` or `NodeIdentify` message, and the public ID it carries if the peer's
+    /// signature of it is valid.
+    Identify(DirectMessage, Result<PublicId, RoutingError>),
+    /// A relayed message and the signed message it contains, whose signature by its originator is
+    /// valid. If the key of the previous node was known when the message was queued, the hop
+    /// signature has been verified against it, and the key is included.
+    Hop(HopMessage, SignedMessage, Option<sign::PublicKey>),
+}
+
+/// An incoming message that has been decoded and checked by a worker thread.
+pub struct CheckedMessage {
+    /// The connection the message was received on.
+    pub connection: crust::Connection,
+    /// The size of the serialised message in bytes.
+    pub size: usize,
+    /// The content, or the reason it was rejected.
+    pub content: Result<CheckedContent, RoutingError>,
+}
+
+/// The result of a job done by a worker thread.
+pub enum Checked {
+    /// An incoming message.
+    Message(CheckedMessage),
+    /// The decrypted endpoints of a node we want to connect to, with the token passed in.
+    Endpoints(u32, Result<Vec<crust::Endpoint>, RoutingError>),
+}
+
+// A message waiting to be checked, with the connection it was received on and the key of the peer
+// on that connection, or endpoints waiting to be decrypted, with their sender's key and a token
+// identifying the connection attempt.
+enum Job {
+    Message(crust::Connection, Vec<u8>, Option<sign::PublicKey>),
+    Endpoints(Vec<u8>, [u8; box_::NONCEBYTES], box_::PublicKey, u32),
+}
+
+/// A pool of worker threads that decode incoming messages, verify their signatures and decrypt
+/// endpoints, so that the routing thread doesn't have to.
+///
+/// All messages from the same connection are checked by the same worker, so they are passed back
+/// in the order they were received. The total size of the messages queued from each connection is
+/// limited, so that a peer can't make the queues grow faster than the workers can empty them.
+///
+/// The worker threads are joined when the `Verifier` is dropped.
+pub struct Verifier {
+    workers: Vec<mpsc::Sender<Job>>,
+    _raii_joiners: Vec<RaiiThreadJoiner>,
+    assignments: HashMap<crust::Connection, usize>,
+    // The total size in bytes of the messages from each connection that are waiting to be checked.
+    queued_sizes: HashMap<crust::Connection, usize>,
+    max_queued_size: usize,
+    // The signing keys of the peers on each connection, as far as we know them.
+    peer_keys: HashMap<crust::Connection, sign::PublicKey>,
+    next_worker: usize,
+}
+
+impl Verifier {
+    /// Start `worker_count` worker threads, which decrypt endpoints with our `encrypting_key`. The
+    /// result of each job is sent to `checked_tx`, followed by an `Action::MessageChecked` to
+    /// notify the routing thread. At most `max_queued_size` bytes of messages from a single
+    /// connection are queued at a time.
+    pub fn new(worker_count: usize,
+               max_queued_size: usize,
+               encrypting_key: box_::SecretKey,
+               checked_tx: mpsc::Sender<Checked>,
+               action_tx: mpsc::Sender<Action>,
+               category_tx: mpsc::Sender<MaidSafeEventCategory>)
+               -> Verifier {
+        let (workers, joiners) = (0..::std::cmp::max(worker_count, 1))
+                          .map(|_| {
+                              let (job_tx, job_rx) = mpsc::channel::<Job>();
+                              let checked_tx = checked_tx.clone();
+                              let encrypting_key = encrypting_key.clone();
+                              let action_sender =
+                                  RoutingActionSender::new(action_tx.clone(),
+                                                           MaidSafeEventCategory::RoutingEvent,
+                                                           category_tx.clone());
+                              // Runs until the `Verifier` or the routing thread has terminated.
+                              let joiner = thread!("RoutingVerifier", move || {
+                                  for job in job_rx.iter() {
+                                      let checked = match job {
+                                          Job::Message(connection, bytes, peer_key) => {
+                                              Checked::Message(CheckedMessage {
+                                                  connection: connection,
+                                                  size: bytes.len(),
+                                                  content: check(&bytes, peer_key.as_ref()),
+                                              })
+                                          }
+                                          Job::Endpoints(encrypted, nonce, their_key, token) => {
+                                              let endpoints =
+                                                  utils::decrypt_endpoints(&encrypted,
+                                                                           nonce,
+                                                                           &their_key,
+                                                                           &encrypting_key);
+                                              Checked::Endpoints(token, endpoints)
+                                          }
+                                      };
+                                      if checked_tx.send(checked).is_err() ||
+                                         action_sender.send(Action::MessageChecked).is_err() {
+                                          break;
+                                      }
+                                  }
+                              });
+                              (job_tx, RaiiThreadJoiner::new(joiner))
+                          })
+                          .unzip();
+        Verifier {
+            workers: workers,
+            _raii_joiners: joiners,
+            assignments: HashMap::new(),
+            queued_sizes: HashMap::new(),
+            max_queued_size: max_queued_size,
+            peer_keys: HashMap::new(),
+            next_worker: 0,
+        }
+    }
+
+    /// Queue the message received on `connection` for checking. Returns `QueueFull` and drops the
+    /// message if it would exceed the size limit of the messages queued from that connection.
+    pub fn check(&mut self,
+                 connection: crust::Connection,
+                 bytes: Vec<u8>)
+                 -> Result<(), RoutingError> {
+        {
+            let queued_size = self.queued_sizes.entry(connection).or_insert(0);
+            if *queued_size + bytes.len() > self.max_queued_size {
+                return Err(RoutingError::QueueFull);
+            }
+            *queued_size += bytes.len();
+        }
+        let worker_count = self.workers.len();
+        let next_worker = &mut self.next_worker;
+        let index = *self.assignments.entry(connection).or_insert_with(|| {
+            let index = *next_worker;
+            *next_worker = (index + 1) % worker_count;
+            index
+        });
+        let peer_key = self.peer_keys.get(&connection).cloned();
+        if self.workers[index].send(Job::Message(connection, bytes, peer_key)).is_err() {
+            error!("Verifier thread for {:?} has terminated.", connection);
+        }
+        Ok(())
+    }
+
+    /// Release the `size` bytes taken up in the queue by a checked message from `connection`.
+    pub fn checked(&mut self, connection: &crust::Connection, size: usize) {
+        if let Some(queued_size) = self.queued_sizes.get_mut(connection) {
+            *queued_size = queued_size.saturating_sub(size);
+        }
+    }
+
+    /// Queue the endpoints encrypted by the node with the key `their_key` for decryption. The
+    /// `token` is passed back with the result.
+    pub fn decrypt_endpoints(&mut self,
+                             encrypted_endpoints: Vec<u8>,
+                             nonce_bytes: [u8; box_::NONCEBYTES],
+                             their_key: box_::PublicKey,
+                             token: u32) {
+        let index = self.next_worker;
+        self.next_worker = (index + 1) % self.workers.len();
+        let job = Job::Endpoints(encrypted_endpoints, nonce_bytes, their_key, token);
+        if self.workers[index].send(job).is_err() {
+            error!("Verifier thread {} has terminated.", index);
+        }
+    }
+
+    /// Set the signing key of the peer on `connection`. The hop signatures of the messages queued
+    /// from then on are verified against it.
+    pub fn set_peer_key(&mut self, connection: crust::Connection, key: sign::PublicKey) {
+        let _ = self.peer_keys.insert(connection, key);
+    }
+
+    /// Forget the worker assigned to the lost `connection` and the key of its peer.
+    pub fn remove(&mut self, connection: &crust::Connection) {
+        let _ = self.assignments.remove(connection);
+        let _ = self.queued_sizes.remove(connection);
+        let _ = self.peer_keys.remove(connection);
+    }
+
+    /// Whether messages from `connection` have been queued, and it hasn't been removed since.
+    pub fn has_connection(&self, connection: &crust::Connection) -> bool {
+        self.assignments.contains_key(connection)
+    }
+}
+
+impl Drop for Verifier {
+    fn drop(&mut self) {
+        // Closing the job queues makes the workers terminate once they have emptied them, before
+        // the joiners wait for them.
+        self.workers.clear();
+    }
+}
+
+/// Decode a received message and check its signatures, as the worker threads do: the originator's
+/// signature of a relayed message's content, the hop signature if the previous node's `peer_key`
+/// is known, and the signature of an identify message's public ID.
+pub fn check(bytes: &[u8],
+             peer_key: Option<&sign::PublicKey>)
+             -> Result<CheckedContent, RoutingError> {
+    match try!(serialisation::deserialise(bytes)) {
+        Message::DirectMessage(direct_msg) => {
+            let public_id = match direct_msg {
+                DirectMessage::ClientIdentify { ref serialised_public_id, ref signature, .. } |
+                DirectMessage::NodeIdentify { ref serialised_public_id, ref signature } => {
+                    Some(utils::verify_signed_public_id(serialised_public_id, signature))
+                }
+                _ => None,
+            };
+            Ok(match public_id {
+                Some(public_id) => CheckedContent::Identify(direct_msg, public_id),
+                None => CheckedContent::Direct(direct_msg),
+            })
+        }
+        Message::HopMessage(hop_msg) => {
+            if let Some(peer_key) = peer_key {
+                try!(hop_msg.verify(peer_key));
+            }
+            let signed_msg = try!(hop_msg.content().signed_message());
+            Ok(CheckedContent::Hop(hop_msg, signed_msg, peer_key.cloned()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate rand;
+
+    use authority::Authority;
+    use error::RoutingError;
+    use id::FullId;
+    use maidsafe_utilities::serialisation;
+    use messages::{DirectMessage, HopContent, HopMessage, Message, RequestContent,
+                   RequestMessage, RoutingMessage, SignedMessage};
+    use sodiumoxide::crypto::sign;
+    use super::{CheckedContent, check};
+
+    #[test]
+    fn check_messages() {
+        let heartbeat = Message::DirectMessage(DirectMessage::Heartbeat);
+        let bytes = unwrap_result!(serialisation::serialise(&heartbeat));
+        match check(&bytes, None) {
+            Ok(CheckedContent::Direct(DirectMessage::Heartbeat)) => (),
+            _ => panic!("Heartbeat not decoded"),
+        }
+
+        let request = RequestMessage {
+            src: Authority::ManagedNode(rand::random()),
+            dst: Authority::NaeManager(rand::random()),
+            content: RequestContent::GetCloseGroup,
+        };
+        let signed_msg = unwrap_result!(SignedMessage::new(RoutingMessage::Request(request),
+                                                           &FullId::new()));
+        let content = unwrap_result!(HopContent::new(&signed_msg));
+        let hop_id = FullId::new();
+        let hop_key = *hop_id.public_id().signing_public_key();
+        let hop_msg = unwrap_result!(HopMessage::new(content,
+                                                     rand::random(),
+                                                     false,
+                                                     1,
+                                                     None,
+                                                     hop_id.signing_private_key()));
+        let bytes = unwrap_result!(serialisation::serialise(&Message::HopMessage(hop_msg)));
+        match check(&bytes, None) {
+            Ok(CheckedContent::Hop(_, checked_msg, None)) => assert_eq!(signed_msg, checked_msg),
+            _ => panic!("Hop message not decoded"),
+        }
+        match check(&bytes, Some(&hop_key)) {
+            Ok(CheckedContent::Hop(_, _, Some(key))) => assert_eq!(hop_key, key),
+            _ => panic!("Hop message not verified"),
+        }
+        match check(&bytes, Some(FullId::new().public_id().signing_public_key())) {
+            Err(RoutingError::FailedSignature) => (),
+            _ => panic!("Hop message from the wrong peer accepted"),
+        }
+
+        match check(&bytes[1..], None) {
+            Err(RoutingError::SerialisationError(_)) => (),
+            _ => panic!("Undecodable message accepted"),
+        }
+    }
+
+    #[test]
+    fn check_identify() {
+        let full_id = FullId::new();
+        let serialised_public_id = unwrap_result!(serialisation::serialise(full_id.public_id()));
+        let signature = sign::sign_detached(&serialised_public_id, full_id.signing_private_key());
+        let node_identify = |signature| {
+            let message = Message::DirectMessage(DirectMessage::NodeIdentify {
+                serialised_public_id: serialised_public_id.clone(),
+                signature: signature,
+            });
+            unwrap_result!(serialisation::serialise(&message))
+        };
+
+        match check(&node_identify(signature), None) {
+            Ok(CheckedContent::Identify(_, Ok(public_id))) => {
+                assert_eq!(*full_id.public_id(), public_id)
+            }
+            _ => panic!("Identify message not verified"),
+        }
+        let other_signature = sign::sign_detached(&serialised_public_id,
+                                                  FullId::new().signing_private_key());
+        match check(&node_identify(other_signature), None) {
+            Ok(CheckedContent::Identify(_, Err(RoutingError::FailedSignature))) => (),
+            _ => panic!("Identify message with an invalid signature accepted"),
+        }
+    }
+}